@@ -0,0 +1,234 @@
+use crate::render::{DrawList, Layer};
+use crate::state::domain::{GameEvent, Position};
+use std::f32::consts::PI;
+use uuid::Uuid;
+
+// Turns resolve instantly, this plays them back: entities slide between tiles and lunge
+// towards whatever they bump into. Durations are in seconds so they don't depend on the
+// frame rate.
+
+const SLIDE_SECONDS: f32 = 0.12;
+const BUMP_SECONDS: f32 = 0.15;
+
+// how far into the next tile a bump reaches, creatures get a proper lunge
+const BUMP_REACH: f32 = 0.15;
+const LUNGE_REACH: f32 = 0.4;
+
+#[derive(Debug, Clone, Copy)]
+enum Motion {
+    Slide {
+        from: Position,
+        to: Position,
+    },
+    Bump {
+        from: Position,
+        towards: Position,
+        reach: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Tween {
+    id: Uuid,
+    motion: Motion,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Tween {
+    // offset from where the entity really is, in tiles
+    fn offset(&self) -> (f32, f32) {
+        let progress = (self.elapsed / self.duration).clamp(0.0, 1.0);
+
+        match self.motion {
+            Motion::Slide { from, to } => {
+                // ease out, quick start and a soft landing
+                let remaining = (1.0 - progress) * (1.0 - progress);
+
+                (
+                    (from.x - to.x) as f32 * remaining,
+                    (from.y - to.y) as f32 * remaining,
+                )
+            }
+            Motion::Bump {
+                from,
+                towards,
+                reach,
+            } => {
+                let out = (progress * PI).sin() * reach;
+
+                (
+                    (towards.x - from.x) as f32 * out,
+                    (towards.y - from.y) as f32 * out,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Animator {
+    tweens: Vec<Tween>,
+}
+
+impl Animator {
+    pub fn new() -> Animator {
+        Animator { tweens: Vec::new() }
+    }
+
+    // anything still playing is dropped, entities jump to where the previous turn left them
+    pub fn start(&mut self, events: &[GameEvent]) {
+        // whoever got teleported just shows up where it landed, a slide would cross the map
+        let teleported: Vec<Uuid> = events
+            .iter()
+            .filter_map(|event| match *event {
+                GameEvent::Teleported { id, .. } => Some(id),
+                _ => None,
+            })
+            .collect();
+
+        self.tweens = events
+            .iter()
+            .filter_map(|event| match *event {
+                GameEvent::Moved { id, from, to } => Some(Tween {
+                    id,
+                    motion: Motion::Slide { from, to },
+                    duration: SLIDE_SECONDS,
+                    elapsed: 0.0,
+                }),
+                GameEvent::Bumped {
+                    id,
+                    from,
+                    towards,
+                    target,
+                } => Some(Tween {
+                    id,
+                    motion: Motion::Bump {
+                        from,
+                        towards,
+                        reach: match target {
+                            Some(_) => LUNGE_REACH,
+                            None => BUMP_REACH,
+                        },
+                    },
+                    duration: BUMP_SECONDS,
+                    elapsed: 0.0,
+                }),
+                _ => None,
+            })
+            .filter(|tween| !teleported.contains(&tween.id))
+            .collect();
+    }
+
+    pub fn update(&mut self, seconds: f32) {
+        for tween in self.tweens.iter_mut() {
+            tween.elapsed += seconds;
+        }
+
+        self.tweens.retain(|tween| tween.elapsed < tween.duration);
+    }
+
+    pub fn is_busy(&self) -> bool {
+        !self.tweens.is_empty()
+    }
+
+    pub fn skip(&mut self) {
+        self.tweens.clear();
+    }
+
+    pub fn apply(&self, list: &mut DrawList) {
+        for item in list
+            .items
+            .iter_mut()
+            .filter(|item| item.layer == Layer::Actor)
+        {
+            for tween in self.tweens.iter().filter(|tween| tween.id == item.id) {
+                let (x, y) = tween.offset();
+                item.offset_x += x;
+                item.offset_y += y;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::render::DrawItem;
+
+    fn get_list(actor: Uuid, pos: Position) -> DrawList<'static> {
+        let item = |id: Uuid, layer: Layer| DrawItem {
+            id,
+            layer,
+            sprite: "ghost",
+            variant: 0,
+            glyph: 'G',
+            color: Color::WHITE,
+            pos,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        };
+
+        DrawList {
+            columns: 5,
+            rows: 5,
+            items: vec![
+                item(Uuid::new_v4(), Layer::Terrain),
+                item(actor, Layer::Actor),
+            ],
+        }
+    }
+
+    fn get_offset(animator: &Animator, actor: Uuid, pos: Position) -> (f32, f32) {
+        let mut list = get_list(actor, pos);
+        animator.apply(&mut list);
+
+        assert_eq!((list.items[0].offset_x, list.items[0].offset_y), (0.0, 0.0));
+        (list.items[1].offset_x, list.items[1].offset_y)
+    }
+
+    #[test]
+    fn moves_slide_in_from_the_previous_tile_and_bumps_lunge_and_come_back() {
+        let id = Uuid::new_v4();
+        let from = Position { x: 1, y: 1 };
+        let to = Position { x: 2, y: 1 };
+        let mut animator = Animator::new();
+
+        animator.start(&[GameEvent::Moved { id, from, to }]);
+        assert!(animator.is_busy());
+        assert_eq!(get_offset(&animator, id, to), (-1.0, 0.0));
+
+        animator.update(SLIDE_SECONDS / 2.0);
+        assert_eq!(get_offset(&animator, id, to), (-0.25, 0.0));
+
+        animator.update(SLIDE_SECONDS);
+        assert!(!animator.is_busy());
+        assert_eq!(get_offset(&animator, id, to), (0.0, 0.0));
+
+        animator.start(&[GameEvent::Bumped {
+            id,
+            from: to,
+            towards: Position { x: 2, y: 0 },
+            target: Some(Uuid::new_v4()),
+        }]);
+        animator.update(BUMP_SECONDS / 2.0);
+        let (x, y) = get_offset(&animator, id, to);
+        assert_eq!(x, 0.0);
+        assert!((y + LUNGE_REACH).abs() < 0.001);
+
+        animator.skip();
+        assert!(!animator.is_busy());
+
+        // a teleport shows up at once where it lands
+        animator.start(&[
+            GameEvent::Moved { id, from, to },
+            GameEvent::Teleported {
+                id,
+                from: to,
+                to: Position { x: 4, y: 4 },
+            },
+        ]);
+        assert!(!animator.is_busy());
+    }
+}
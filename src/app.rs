@@ -0,0 +1,330 @@
+use crate::state::domain::{Command, Game, Hud};
+use crate::user_settings::{UserOption, UserSettings};
+
+// Which screen the game is on, every screen but Playing is a menu. Menus are moved through
+// with the same up and down keys as the map, Enter picks an entry and Escape backs out. The
+// left and right keys step options back and forth.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    Title,
+    Playing,
+    Paused,
+    Options,
+    GameOver,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    NewGame,
+    Continue,
+    Resume,
+    Options,
+    ToggleAnimations,
+    ToggleDebug,
+    ToggleMinimap,
+    // an option of the settings file, and which way to step it
+    Change(UserOption, i32),
+    Back,
+    SaveAndQuit,
+    Quit,
+}
+
+#[derive(Debug, Clone)]
+pub struct MenuItem {
+    pub label: String,
+    pub action: MenuAction,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct App {
+    pub screen: Screen,
+    // where the options screen goes back to
+    pub options_from: Screen,
+    pub selected: usize,
+    // shown under the menu, ex: a save that could not be loaded
+    pub notice: Option<String>,
+    pub quit: bool,
+}
+
+fn item(label: &str, action: MenuAction) -> MenuItem {
+    MenuItem {
+        label: label.to_string(),
+        action,
+        enabled: true,
+    }
+}
+
+fn toggle(label: &str, enabled: bool, action: MenuAction) -> MenuItem {
+    item(
+        &format!("{}: {}", label, if enabled { "on" } else { "off" }),
+        action,
+    )
+}
+
+impl Default for App {
+    fn default() -> App {
+        App::new()
+    }
+}
+
+impl App {
+    pub fn new() -> App {
+        App {
+            screen: Screen::Title,
+            options_from: Screen::Title,
+            selected: 0,
+            notice: None,
+            quit: false,
+        }
+    }
+
+    pub fn go_to(&mut self, screen: Screen) {
+        if screen == Screen::Options {
+            self.options_from = self.screen;
+        }
+
+        self.screen = screen;
+        self.selected = 0;
+    }
+
+    // a game is going on, even if paused, as opposed to sitting in the title or game over menus
+    pub fn in_game(&self) -> bool {
+        match self.screen {
+            Screen::Playing | Screen::Paused => true,
+            Screen::Options => self.options_from == Screen::Paused,
+            Screen::Title | Screen::GameOver => false,
+        }
+    }
+
+    pub fn title(&self, game: &Game) -> String {
+        match self.screen {
+            Screen::Title => "Poorguelike".to_string(),
+            Screen::Playing | Screen::Paused => "Paused".to_string(),
+            Screen::Options => "Options".to_string(),
+            Screen::GameOver => format!("You died on turn {}", game.turn),
+        }
+    }
+
+    pub fn menu(
+        &self,
+        game: &Game,
+        hud: &Hud,
+        user: &UserSettings,
+        has_save: bool,
+    ) -> Vec<MenuItem> {
+        let option =
+            |option: UserOption| item(&user.describe(option), MenuAction::Change(option, 1));
+
+        match self.screen {
+            Screen::Title => vec![
+                MenuItem {
+                    enabled: has_save,
+                    ..item("Continue", MenuAction::Continue)
+                },
+                item("New game", MenuAction::NewGame),
+                item("Options", MenuAction::Options),
+                item("Quit", MenuAction::Quit),
+            ],
+            Screen::Playing => Vec::new(),
+            Screen::Paused => vec![
+                item("Resume", MenuAction::Resume),
+                item("Options", MenuAction::Options),
+                item("Save and quit to title", MenuAction::SaveAndQuit),
+            ],
+            Screen::Options => vec![
+                option(UserOption::WindowSize),
+                option(UserOption::TileScale),
+                option(UserOption::MapSize),
+                option(UserOption::FontSize),
+                option(UserOption::Keys),
+                option(UserOption::Palette),
+                toggle(
+                    "Animations",
+                    game.settings.animations,
+                    MenuAction::ToggleAnimations,
+                ),
+                toggle(
+                    "Debug logging",
+                    game.settings.debug,
+                    MenuAction::ToggleDebug,
+                ),
+                toggle("Minimap", hud.minimap, MenuAction::ToggleMinimap),
+                item("Back", MenuAction::Back),
+            ],
+            Screen::GameOver => vec![
+                item("New game", MenuAction::NewGame),
+                item("Quit", MenuAction::Quit),
+            ],
+        }
+    }
+
+    fn step_selection(&mut self, items: &[MenuItem], step: isize) {
+        let count = items.len() as isize;
+
+        // skips disabled entries, never looping more than once around the menu
+        for _ in 0..count {
+            self.selected = (self.selected as isize + step).rem_euclid(count.max(1)) as usize;
+
+            if items[self.selected].enabled {
+                return;
+            }
+        }
+    }
+
+    // keeps the selection on an entry that can be picked, ex: Continue without a save
+    pub fn settle_selection(&mut self, items: &[MenuItem]) {
+        if items.is_empty() {
+            return;
+        }
+
+        self.selected = self.selected.min(items.len() - 1);
+
+        if !items[self.selected].enabled {
+            self.step_selection(items, 1);
+        }
+    }
+
+    // what the menu on screen wants done, it's up to the caller to do it
+    pub fn handle_menu(&mut self, command: Command, items: &[MenuItem]) -> Option<MenuAction> {
+        if items.is_empty() {
+            return None;
+        }
+
+        match command {
+            Command::MoveUp => {
+                self.step_selection(items, -1);
+                None
+            }
+            Command::MoveDown => {
+                self.step_selection(items, 1);
+                None
+            }
+            Command::MoveLeft | Command::MoveRight => match items.get(self.selected) {
+                Some(MenuItem {
+                    action: MenuAction::Change(option, _),
+                    ..
+                }) => Some(MenuAction::Change(
+                    *option,
+                    match command {
+                        Command::MoveLeft => -1,
+                        _ => 1,
+                    },
+                )),
+                _ => None,
+            },
+            Command::Confirm => items
+                .get(self.selected)
+                .filter(|item| item.enabled)
+                .map(|item| item.action),
+            Command::Cancel => match self.screen {
+                Screen::Paused => Some(MenuAction::Resume),
+                Screen::Options => Some(MenuAction::Back),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the title menu without a save to continue
+    fn get_title_items() -> Vec<MenuItem> {
+        vec![
+            MenuItem {
+                enabled: false,
+                ..item("Continue", MenuAction::Continue)
+            },
+            item("New game", MenuAction::NewGame),
+            item("Options", MenuAction::Options),
+            item("Quit", MenuAction::Quit),
+        ]
+    }
+
+    #[test]
+    fn the_selection_skips_disabled_entries_and_wraps_around() {
+        let items = get_title_items();
+        let mut app = App::new();
+
+        app.settle_selection(&items);
+        assert_eq!(app.selected, 1);
+
+        // up from New game goes past Continue, all the way around to Quit
+        assert_eq!(app.handle_menu(Command::MoveUp, &items), None);
+        assert_eq!(app.selected, 3);
+
+        assert_eq!(app.handle_menu(Command::MoveDown, &items), None);
+        assert_eq!(app.selected, 1);
+
+        app.selected = 0;
+        assert_eq!(app.handle_menu(Command::Confirm, &items), None);
+
+        app.selected = 1;
+        assert_eq!(
+            app.handle_menu(Command::Confirm, &items),
+            Some(MenuAction::NewGame)
+        );
+    }
+
+    #[test]
+    fn options_go_back_to_the_screen_they_were_opened_from() {
+        let items = vec![item("Back", MenuAction::Back)];
+        let mut app = App::new();
+
+        app.go_to(Screen::Options);
+        assert_eq!(app.options_from, Screen::Title);
+        assert!(!app.in_game());
+        assert_eq!(
+            app.handle_menu(Command::Cancel, &items),
+            Some(MenuAction::Back)
+        );
+
+        app.go_to(Screen::Playing);
+        app.go_to(Screen::Paused);
+        assert_eq!(
+            app.handle_menu(Command::Cancel, &items),
+            Some(MenuAction::Resume)
+        );
+
+        app.selected = 2;
+        app.go_to(Screen::Options);
+        assert_eq!(app.options_from, Screen::Paused);
+        assert_eq!(app.selected, 0);
+        assert!(app.in_game());
+
+        app.go_to(app.options_from);
+        assert_eq!(app.screen, Screen::Paused);
+        assert_eq!(
+            app.handle_menu(Command::Cancel, &items),
+            Some(MenuAction::Resume)
+        );
+    }
+
+    #[test]
+    fn left_and_right_only_step_options() {
+        let items = vec![
+            item("Window size", MenuAction::Change(UserOption::WindowSize, 1)),
+            item("Back", MenuAction::Back),
+        ];
+        let mut app = App::new();
+        app.go_to(Screen::Options);
+
+        assert_eq!(
+            app.handle_menu(Command::MoveLeft, &items),
+            Some(MenuAction::Change(UserOption::WindowSize, -1))
+        );
+        assert_eq!(
+            app.handle_menu(Command::MoveRight, &items),
+            Some(MenuAction::Change(UserOption::WindowSize, 1))
+        );
+
+        app.handle_menu(Command::MoveDown, &items);
+        assert_eq!(app.handle_menu(Command::MoveLeft, &items), None);
+        assert_eq!(app.handle_menu(Command::MoveRight, &items), None);
+        assert_eq!(app.selected, 1);
+    }
+}
@@ -0,0 +1,357 @@
+use poorguelike::content::creatures as Creatures;
+use poorguelike::content::creatures::{CreatureTemplate, CREATURES_FILE};
+use poorguelike::content::dialogues as Dialogues;
+use poorguelike::content::dialogues::DIALOGUES_FILE;
+use poorguelike::content::tileset as TilesetContent;
+use poorguelike::content::tileset::{Tileset, TILESET_FILE};
+use poorguelike::diagnostics as Diagnostics;
+use poorguelike::diagnostics::{Level, TARGET_GAME, TARGET_INPUT};
+use poorguelike::render as Render;
+use poorguelike::render::ascii::AsciiRenderer;
+use poorguelike::render::Renderer;
+use poorguelike::state::core as StateCore;
+use poorguelike::state::dialogue as Talking;
+use poorguelike::state::domain::{Command, Game, Hud, Key, MessageKind};
+use poorguelike::state::helpers as StateHelpers;
+use poorguelike::state::travel as Travelling;
+use poorguelike::user_settings as UserSettingsFile;
+use std::io::{self, Read, Write};
+use std::process::{Command as Shell, Stdio};
+use std::thread;
+use std::time::Duration;
+
+// Same game as the window version, drawn with characters and ansi colours and played from
+// the terminal, so it works over ssh on machines without a display
+
+// pause between two steps of a walk, so it can be followed
+const STEP_DELAY: Duration = Duration::from_millis(40);
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    // and whether shift was held
+    Key(Key, bool),
+    Quit,
+}
+
+// puts the terminal in raw mode (keys arrive one by one, nothing is echoed) and on an
+// alternate screen, everything is put back when dropped
+struct RawTerminal {
+    saved: String,
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Shell::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+impl RawTerminal {
+    fn enable() -> io::Result<RawTerminal> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+
+        Ok(RawTerminal { saved })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn read_input(stdin: &mut io::Stdin) -> io::Result<Option<Input>> {
+    let mut buffer = [0u8; 16];
+    let read = stdin.read(&mut buffer)?;
+
+    Ok(decode_input(&buffer[..read]))
+}
+
+// the keys are translated to the game's own so both frontends share get_command_for_key
+fn decode_input(bytes: &[u8]) -> Option<Input> {
+    let key = |key: Key| Some(Input::Key(key, false));
+    let shifted = |key: Key| Some(Input::Key(key, true));
+
+    match bytes {
+        [] | [b'q'] | [3] => Some(Input::Quit),
+        [0x1b] => key(Key::Escape),
+        b"\x1b[A" | b"\x1bOA" => key(Key::Up),
+        b"\x1b[B" | b"\x1bOB" => key(Key::Down),
+        b"\x1b[C" | b"\x1bOC" => key(Key::Right),
+        b"\x1b[D" | b"\x1bOD" => key(Key::Left),
+        b"\x1b[1;2A" => shifted(Key::Up),
+        b"\x1b[1;2B" => shifted(Key::Down),
+        b"\x1b[1;2C" => shifted(Key::Right),
+        b"\x1b[1;2D" => shifted(Key::Left),
+        b"\x1b[5~" => key(Key::PageUp),
+        b"\x1b[6~" => key(Key::PageDown),
+        b"\x1bOR" | b"\x1b[13~" => key(Key::F3),
+        [byte] => StateCore::get_key_for_char(*byte as char)
+            .map(|key| Input::Key(key, byte.is_ascii_uppercase())),
+        _ => None,
+    }
+}
+
+fn screen(game: &Game, hud: &Hud, tileset: &Tileset, renderer: &mut AsciiRenderer) -> String {
+    let mut lines: Vec<String> = Vec::new();
+
+    renderer.render(&Render::draw_list(game, tileset));
+    lines.extend(renderer.output().lines().map(|line| line.to_string()));
+    lines.push(String::new());
+
+    if game.conversation.is_some() {
+        lines.extend(Talking::describe(game));
+    } else if let Some(cursor) = hud.look {
+        lines.extend(StateHelpers::describe_tile(game, &cursor));
+        lines.push("[x] stop looking".to_string());
+    } else {
+        let last = game.messages.len().saturating_sub(hud.log_scroll);
+        let first = last.saturating_sub(game.settings.hud.log_lines as usize);
+
+        for message in game.messages[first..last].iter() {
+            let line = format!("[{}] {}", message.turn, message.text);
+
+            lines.push(match message.kind {
+                MessageKind::Info => line,
+                MessageKind::Warning => format!("\x1b[33m{}\x1b[0m", line),
+            });
+        }
+
+        if hud.log_scroll > 0 {
+            lines.push(format!("-- {} newer --", hud.log_scroll));
+        }
+    }
+
+    lines.push("[q] quit".to_string());
+
+    // raw mode does not return the carriage on its own
+    let mut output = format!("\x1b[H\x1b[2J{}", lines.join("\x1b[K\r\n"));
+
+    // the look cursor is the terminal cursor, sitting on the tile being examined
+    match hud.look {
+        Some(cursor) => {
+            output.push_str(&format!("\x1b[{};{}H\x1b[?25h", cursor.y + 1, cursor.x + 1))
+        }
+        None => output.push_str("\x1b[?25l"),
+    }
+
+    output
+}
+
+fn load_content() -> Result<(Tileset, Vec<CreatureTemplate>), String> {
+    let tileset = TilesetContent::load_tileset(TILESET_FILE).map_err(|error| error.to_string())?;
+    let dialogues = Dialogues::load_dialogues(DIALOGUES_FILE).map_err(|error| error.to_string())?;
+    let creatures = Creatures::load_creatures(CREATURES_FILE, &tileset, &dialogues)
+        .map_err(|error| error.to_string())?;
+
+    Ok((tileset, creatures))
+}
+
+fn main() {
+    Diagnostics::init_from_env();
+
+    let user = UserSettingsFile::load_or_default(&UserSettingsFile::settings_path());
+
+    let (tileset, creatures) = match load_content() {
+        Ok(content) => content,
+        Err(error) => {
+            Diagnostics::log(
+                Level::Error,
+                TARGET_GAME,
+                "could not load content",
+                &[("error", &error)],
+            );
+            std::process::exit(1);
+        }
+    };
+
+    // only the map size, keys and palette matter here, a terminal has no pixels to size
+    let settings = user.to_settings(user.rows, user.columns, 1);
+    let mut game = StateCore::new_game(settings, &creatures);
+
+    let mut hud = Hud {
+        log_scroll: 0,
+        look: None,
+        last_mouse_tile: None,
+        minimap: false,
+        count: 0,
+    };
+
+    let terminal = match RawTerminal::enable() {
+        Ok(terminal) => terminal,
+        Err(error) => {
+            Diagnostics::log(
+                Level::Error,
+                TARGET_GAME,
+                "could not set up the terminal",
+                &[("error", &error)],
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let mut renderer = AsciiRenderer::new(true);
+    let mut stdin = io::stdin();
+    let mut stdout = io::stdout();
+
+    loop {
+        let _ = write!(stdout, "{}", screen(&game, &hud, &tileset, &mut renderer));
+        let _ = stdout.flush();
+
+        let (command, run) = match read_input(&mut stdin) {
+            Ok(Some(Input::Key(key, shifted))) => (
+                StateCore::get_command_for_key(&key, game.settings.keys),
+                shifted,
+            ),
+            Ok(Some(Input::Quit)) | Err(_) => break,
+            Ok(None) => (None, false),
+        };
+
+        if let Some(command) = command {
+            Diagnostics::log(
+                Level::Debug,
+                TARGET_INPUT,
+                "command",
+                &[("command", &format!("{:?}", command)), ("turn", &game.turn)],
+            );
+
+            // a count only lasts until the next command
+            let count = match command {
+                Command::Count(_) => 0,
+                _ => std::mem::take(&mut hud.count),
+            };
+
+            let travel = match hud.look.is_none() {
+                true => Travelling::start_for_command(&game, command, count, run),
+                false => None,
+            };
+
+            if let Some(mut travel) = travel {
+                // every step is drawn and held for a moment, keys pressed meanwhile wait
+                // until the walk is over
+                while let Some(step) = Travelling::take_travel_step(&mut game, &mut travel) {
+                    game = StateCore::update_game_states(&game, &game.settings, step);
+
+                    if StateHelpers::get_player(&game).is_none() {
+                        break;
+                    }
+
+                    let _ = write!(stdout, "{}", screen(&game, &hud, &tileset, &mut renderer));
+                    let _ = stdout.flush();
+                    thread::sleep(STEP_DELAY);
+                }
+
+                hud.log_scroll = 0;
+            } else if StateCore::is_gameplay_command(&command, &hud, &game) {
+                game = StateCore::update_game_states(&game, &game.settings, command);
+                hud.log_scroll = 0;
+            } else {
+                StateCore::update_hud(&mut hud, &mut game, command);
+            }
+        }
+
+        if StateHelpers::get_player(&game).is_none() {
+            break;
+        }
+    }
+
+    // printed once the terminal is back to normal, so it stays on screen
+    drop(terminal);
+
+    if StateHelpers::get_player(&game).is_none() {
+        println!("You died on turn {}.", game.turn);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poorguelike::color::Color;
+    use poorguelike::content::parser as Parser;
+    use poorguelike::state::domain::{EntityAi, EntityRelationship};
+
+    const TILESET: &str = "[atlas terrain]
+image = terrain.png
+tile_size = 16
+";
+
+    #[test]
+    fn terminal_keys_become_the_same_keys_as_in_the_window() {
+        assert_eq!(
+            decode_input(b"l"),
+            Some(Input::Key(Key::Letter('L'), false))
+        );
+        assert_eq!(decode_input(b"L"), Some(Input::Key(Key::Letter('L'), true)));
+        assert_eq!(decode_input(b"."), Some(Input::Key(Key::Period, false)));
+        assert_eq!(decode_input(b"\x1b[A"), Some(Input::Key(Key::Up, false)));
+        assert_eq!(
+            decode_input(b"\x1b[1;2C"),
+            Some(Input::Key(Key::Right, true))
+        );
+        assert_eq!(decode_input(b"\x1b"), Some(Input::Key(Key::Escape, false)));
+        assert_eq!(decode_input(b"q"), Some(Input::Quit));
+        assert_eq!(decode_input(&[3]), Some(Input::Quit));
+        assert_eq!(decode_input(b""), Some(Input::Quit));
+        assert_eq!(decode_input(b"\x1b[Z"), None);
+    }
+
+    #[test]
+    fn the_screen_shows_the_log_or_what_is_being_looked_at() {
+        let tileset = Parser::parse("tileset.cfg", TILESET)
+            .and_then(|sections| TilesetContent::parse_tileset("tileset.cfg", &sections))
+            .unwrap();
+        let player = CreatureTemplate {
+            id: "player".to_string(),
+            name: "you".to_string(),
+            face: "player".to_string(),
+            color: Color::WHITE,
+            health: 10,
+            attack: 2,
+            ai: EntityAi::Player,
+            relationship: EntityRelationship::Neutral,
+            depth: 0,
+            opens_doors: false,
+            swims: false,
+            digs: false,
+            dialogue: None,
+        };
+        let game = StateCore::new_game(StateHelpers::get_settings(10, 10, 10, 10, 1), &[player]);
+        let mut renderer = AsciiRenderer::new(false);
+        let mut hud = Hud {
+            log_scroll: 0,
+            look: None,
+            last_mouse_tile: None,
+            minimap: false,
+            count: 0,
+        };
+
+        let output = screen(&game, &hud, &tileset, &mut renderer);
+        let lines: Vec<&str> = output.split("\x1b[K\r\n").collect();
+
+        assert!(lines[0].starts_with("\x1b[H\x1b[2J"));
+        assert_eq!(lines.len(), 10 + 1 + game.messages.len() + 1);
+        assert_eq!(lines[11], format!("[0] {}", game.messages[0].text));
+        assert_eq!(lines[12], "[q] quit\x1b[?25l");
+
+        let player = StateHelpers::get_player(&game).unwrap().pos;
+        hud.look = Some(player);
+
+        let output = screen(&game, &hud, &tileset, &mut renderer);
+        assert!(output.contains(&format!("Tile x: {}, y: {}", player.x, player.y)));
+        assert!(output.contains("[x] stop looking"));
+        assert!(output.ends_with(&format!("\x1b[{};{}H\x1b[?25h", player.y + 1, player.x + 1)));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::state::domain::{Generator, Settings};
+use crate::state::helpers as StateHelpers;
+use crate::user_settings as UserSettingsFile;
+use std::str::FromStr;
+
+// Command line options of the window version, anything given here wins over the settings
+// file for this run only, it is never written back
+
+pub const USAGE: &str = "\
+Usage: poorguelike [options]
+
+Options:
+  --seed <number>      seed of the first new game, the same seed gives the same game
+  --map-size <WxH>     map size in tiles, ex: 30x24
+  --window <WxH>       window size in pixels, ex: 960x960
+  --generator <name>   map layout, arena (a single room) or rooms
+  --mobs <count>       monsters spawned in a new game
+  --npcs <count>       villagers spawned in a new game
+  --load <save>        play that save right away, saving back to it
+  --replay <file>      play a recorded game back (the last game is kept in poorguelike.replay)
+  --headless           no window: print the map, turn and log, then quit. A new game (alone or
+                       with --seed) or a --load save is shown as it starts, a --replay once it
+                       has been played through
+  --help               show this and quit
+";
+
+// the smallest map that still has a tile to stand on inside its walls
+const MIN_MAP_SIZE: i32 = 3;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    pub seed: Option<u64>,
+    // columns and rows
+    pub map_size: Option<(i32, i32)>,
+    // width and height
+    pub window: Option<(i32, i32)>,
+    pub generator: Option<Generator>,
+    pub mobs: Option<i32>,
+    pub npcs: Option<i32>,
+    pub load: Option<String>,
+    pub replay: Option<String>,
+    pub headless: bool,
+    pub help: bool,
+}
+
+fn invalid(option: &str, value: &str) -> String {
+    format!("invalid value for {}: '{}'", option, value)
+}
+
+fn parse_number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| invalid(option, value))
+}
+
+fn parse_count(option: &str, value: &str) -> Result<i32, String> {
+    match parse_number(option, value)? {
+        count if count >= 0 => Ok(count),
+        _ => Err(invalid(option, value)),
+    }
+}
+
+fn parse_size(option: &str, value: &str) -> Result<(i32, i32), String> {
+    UserSettingsFile::parse_size(value).ok_or_else(|| invalid(option, value))
+}
+
+fn parse_map_size(option: &str, value: &str) -> Result<(i32, i32), String> {
+    match parse_size(option, value)? {
+        (columns, rows) if columns >= MIN_MAP_SIZE && rows >= MIN_MAP_SIZE => Ok((columns, rows)),
+        _ => Err(format!(
+            "{} should be at least {}x{}, found '{}'",
+            option, MIN_MAP_SIZE, MIN_MAP_SIZE, value
+        )),
+    }
+}
+
+// every tile needs at least a pixel of the window
+fn check_fits(window: (i32, i32), map: (i32, i32)) -> Result<(), String> {
+    let ((width, height), (columns, rows)) = (window, map);
+
+    match width >= columns && height >= rows {
+        true => Ok(()),
+        false => Err(format!(
+            "a {}x{} window is too small for a {}x{} map, it needs at least a pixel per tile",
+            width, height, columns, rows
+        )),
+    }
+}
+
+fn parse_generator(option: &str, value: &str) -> Result<Generator, String> {
+    match value {
+        "arena" => Ok(Generator::Arena),
+        "rooms" => Ok(Generator::Rooms),
+        _ => Err(invalid(option, value)),
+    }
+}
+
+// both "--seed 42" and "--seed=42" work, the program name is expected to be left out
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = match arg.split_once('=') {
+            Some((option, value)) => (option, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next().cloned())
+                .ok_or_else(|| format!("missing value for {}", option))
+        };
+
+        match option {
+            "--seed" => options.seed = Some(parse_number(option, &value()?)?),
+            "--map-size" => options.map_size = Some(parse_map_size(option, &value()?)?),
+            "--window" => options.window = Some(parse_size(option, &value()?)?),
+            "--generator" => options.generator = Some(parse_generator(option, &value()?)?),
+            "--mobs" => options.mobs = Some(parse_count(option, &value()?)?),
+            "--npcs" => options.npcs = Some(parse_count(option, &value()?)?),
+            "--load" => options.load = Some(value()?),
+            "--replay" => options.replay = Some(value()?),
+            "--headless" => options.headless = true,
+            "--help" | "-h" => options.help = true,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+
+    if options.load.is_some() && options.replay.is_some() {
+        return Err("--load and --replay can't be used together".to_string());
+    }
+
+    if let (Some(window), Some(map)) = (options.window, options.map_size) {
+        check_fits(window, map)?;
+    }
+
+    Ok(options)
+}
+
+// the options may only give one of the map and the window sizes, the other one comes from
+// the settings file, so they can only be checked against each other once put together
+pub fn check_settings(settings: &Settings) -> Result<(), String> {
+    check_fits(
+        (settings.window.width, settings.window.height),
+        (settings.graphic.columns, settings.graphic.rows),
+    )
+}
+
+impl Options {
+    // the window size and what new games are made of, the map size is left to the caller
+    // since a loaded game keeps its own
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        let mut settings = match self.window {
+            Some((width, height)) => StateHelpers::resize_window(settings, height, width),
+            None => settings.to_owned(),
+        };
+
+        settings.world.generator = self.generator.unwrap_or(settings.world.generator);
+        settings.world.mobs = self.mobs.unwrap_or(settings.world.mobs);
+        settings.world.npcs = self.npcs.unwrap_or(settings.world.npcs);
+
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_are_read_with_or_without_an_equal_sign() {
+        let options = parse_args(&args(
+            "--seed 42 --map-size=30x24 --generator rooms --mobs=0 --headless",
+        ))
+        .unwrap();
+
+        assert_eq!(options.seed, Some(42));
+        assert_eq!(options.map_size, Some((30, 24)));
+        assert_eq!(options.generator, Some(Generator::Rooms));
+        assert_eq!(options.mobs, Some(0));
+        assert!(options.headless);
+
+        assert!(parse_args(&args("--seed")).is_err());
+        assert!(parse_args(&args("--mobs -1")).is_err());
+        assert!(parse_args(&args("--nope")).is_err());
+    }
+
+    #[test]
+    fn maps_too_small_to_stand_in_or_too_big_for_the_window_are_refused() {
+        assert_eq!(
+            parse_args(&args("--map-size 2x2")),
+            Err("--map-size should be at least 3x3, found '2x2'".to_string())
+        );
+        assert!(parse_args(&args("--map-size 1x1")).is_err());
+        assert!(parse_args(&args("--map-size 3x3")).is_ok());
+
+        assert_eq!(
+            parse_args(&args("--map-size 2000x2000 --window 800x800")),
+            Err(
+                "a 800x800 window is too small for a 2000x2000 map, it needs at least a pixel per tile"
+                    .to_string()
+            )
+        );
+        assert!(parse_args(&args("--window 1x1 --map-size 30x24")).is_err());
+        assert!(parse_args(&args("--window 30x24 --map-size 30x24")).is_ok());
+
+        // with only one of them given, the other one comes from the settings
+        let settings = StateHelpers::get_settings(800, 800, 24, 30, 26);
+        assert!(check_settings(&settings).is_ok());
+        assert!(check_settings(&StateHelpers::resize_window(&settings, 1, 1)).is_err());
+    }
+}
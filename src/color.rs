@@ -0,0 +1,50 @@
+use std::num::ParseIntError;
+
+// Colours as the game keeps them, so the core doesn't depend on any frontend. The raylib one
+// turns them into its own colours when drawing (see draw.rs), the ascii one into escape codes.
+// The named ones have the same values as raylib's.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::new(255, 255, 255, 255);
+    pub const BLACK: Color = Color::new(0, 0, 0, 255);
+    pub const GRAY: Color = Color::new(130, 130, 130, 255);
+    pub const DARKGRAY: Color = Color::new(80, 80, 80, 255);
+    pub const RED: Color = Color::new(230, 41, 55, 255);
+    pub const ORANGE: Color = Color::new(255, 161, 0, 255);
+    pub const GOLD: Color = Color::new(255, 203, 0, 255);
+    pub const YELLOW: Color = Color::new(253, 249, 0, 255);
+    pub const SKYBLUE: Color = Color::new(102, 191, 255, 255);
+    pub const BLUE: Color = Color::new(0, 121, 241, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+
+    // "RRGGBB", always fully opaque
+    pub fn from_hex(hex: &str) -> Result<Color, ParseIntError> {
+        let value = u32::from_str_radix(hex, 16)?;
+
+        Ok(Color::new(
+            (value >> 16) as u8,
+            (value >> 8) as u8,
+            value as u8,
+            255,
+        ))
+    }
+
+    // alpha goes from 0.0 (invisible) to 1.0 (fully opaque)
+    pub fn fade(&self, alpha: f32) -> Color {
+        Color {
+            a: (255.0 * alpha.clamp(0.0, 1.0)) as u8,
+            ..*self
+        }
+    }
+}
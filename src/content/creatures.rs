@@ -0,0 +1,299 @@
+use crate::color::Color;
+use crate::content::parser::{self as Parser, Section};
+use crate::content::tileset::Tileset;
+use crate::content::ContentError;
+use crate::state::domain::{Dialogue, EntityAi, EntityMode, EntityRelationship};
+
+pub const CREATURES_FILE: &str = "./content/creatures.cfg";
+
+const CREATURE_KEYS: [&str; 12] = [
+    "name",
+    "tile",
+    "color",
+    "health",
+    "attack",
+    "ai",
+    "relationship",
+    "depth",
+    "opens_doors",
+    "swims",
+    "digs",
+    "dialogue",
+];
+
+#[derive(Debug, Clone)]
+pub struct CreatureTemplate {
+    pub id: String,
+    pub name: String,
+    pub face: String,
+    pub color: Color,
+    pub health: i32,
+    pub attack: i32,
+    pub ai: EntityAi,
+    pub relationship: EntityRelationship,
+    pub depth: i32,
+    pub opens_doors: bool,
+    pub swims: bool,
+    pub digs: bool,
+    // what it says when the player walks into it, friendly creatures only
+    pub dialogue: Option<Dialogue>,
+}
+
+impl CreatureTemplate {
+    pub fn mode(&self) -> EntityMode {
+        match (self.ai, self.relationship) {
+            (EntityAi::Player, _) => EntityMode::Player,
+            (_, EntityRelationship::Foe) => EntityMode::Mob,
+            _ => EntityMode::NPC,
+        }
+    }
+}
+
+// optional true or false, false when left out
+fn get_flag(section: &Section, key: &str) -> Result<bool, ContentError> {
+    match section.get(key) {
+        Some(entry) => entry.value.parse().map_err(|_| {
+            section.error(
+                entry.line,
+                format!("{} should be true or false, found '{}'", key, entry.value),
+            )
+        }),
+        None => Ok(false),
+    }
+}
+
+fn parse_creature(
+    section: &Section,
+    tileset: &Tileset,
+    dialogues: &[Dialogue],
+) -> Result<CreatureTemplate, ContentError> {
+    section.only_keys(&CREATURE_KEYS)?;
+
+    let tile = section.require("tile")?;
+    if !tileset.has_sprite(&tile.value) {
+        return Err(section.error(tile.line, format!("unknown sprite '{}'", tile.value)));
+    }
+
+    let ai = section.require("ai")?;
+    let ai_kind = match ai.value.as_str() {
+        "player" => EntityAi::Player,
+        "wander" => EntityAi::Wander,
+        _ => {
+            return Err(section.error(
+                ai.line,
+                format!("unknown ai '{}', expected player or wander", ai.value),
+            ));
+        }
+    };
+
+    let relationship = section.require("relationship")?;
+    let relationship_kind = match relationship.value.as_str() {
+        "foe" => EntityRelationship::Foe,
+        "friendly" => EntityRelationship::Friendly,
+        "neutral" => EntityRelationship::Neutral,
+        _ => {
+            return Err(section.error(
+                relationship.line,
+                format!(
+                    "unknown relationship '{}', expected foe, friendly or neutral",
+                    relationship.value
+                ),
+            ));
+        }
+    };
+
+    let health = section.require_int("health")?;
+    if health <= 0 {
+        return Err(section.error(
+            section.require("health")?.line,
+            format!("health should be above zero, found {}", health),
+        ));
+    }
+
+    let attack = section.require_int("attack")?;
+    if attack < 0 {
+        return Err(section.error(
+            section.require("attack")?.line,
+            format!("attack can't be negative, found {}", attack),
+        ));
+    }
+
+    let depth = section.require_int("depth")?;
+    if depth < 0 {
+        return Err(section.error(
+            section.require("depth")?.line,
+            format!("depth can't be negative, found {}", depth),
+        ));
+    }
+
+    // the player starts on the first level, it never spawns anywhere else
+    if ai_kind == EntityAi::Player && depth != 0 {
+        return Err(section.error(
+            section.require("depth")?.line,
+            format!("the player's depth should be 0, found {}", depth),
+        ));
+    }
+
+    let dialogue = match section.get("dialogue") {
+        // only the friendly ones get talked to, see state::dialogue
+        Some(entry) if !matches!(relationship_kind, EntityRelationship::Friendly) => {
+            return Err(section.error(
+                entry.line,
+                format!(
+                    "only friendly creatures can talk, this one is '{}'",
+                    section.require("relationship")?.value
+                ),
+            ))
+        }
+        Some(entry) => match dialogues.iter().find(|dialogue| dialogue.id == entry.value) {
+            Some(dialogue) => Some(dialogue.to_owned()),
+            None => {
+                return Err(section.error(entry.line, format!("unknown dialogue '{}'", entry.value)))
+            }
+        },
+        None => None,
+    };
+
+    Ok(CreatureTemplate {
+        id: section.name.to_owned(),
+        name: section.require("name")?.value.to_owned(),
+        face: tile.value.to_owned(),
+        color: section.require_color("color")?,
+        health,
+        attack,
+        ai: ai_kind,
+        relationship: relationship_kind,
+        depth,
+        opens_doors: get_flag(section, "opens_doors")?,
+        swims: get_flag(section, "swims")?,
+        digs: get_flag(section, "digs")?,
+        dialogue,
+    })
+}
+
+pub fn parse_creatures(
+    file: &str,
+    sections: &[Section],
+    tileset: &Tileset,
+    dialogues: &[Dialogue],
+) -> Result<Vec<CreatureTemplate>, ContentError> {
+    let mut creatures: Vec<CreatureTemplate> = Vec::new();
+
+    for section in sections {
+        creatures.push(parse_creature(section, tileset, dialogues)?);
+    }
+
+    let players: Vec<&CreatureTemplate> = creatures
+        .iter()
+        .filter(|creature| creature.ai == EntityAi::Player)
+        .collect();
+
+    if players.len() != 1 {
+        return Err(ContentError {
+            file: file.to_string(),
+            line: 0,
+            entry: None,
+            message: format!(
+                "exactly one creature should have 'ai = player', found {}",
+                players.len()
+            ),
+        });
+    }
+
+    Ok(creatures)
+}
+
+pub fn load_creatures(
+    path: &str,
+    tileset: &Tileset,
+    dialogues: &[Dialogue],
+) -> Result<Vec<CreatureTemplate>, ContentError> {
+    parse_creatures(path, &Parser::read_file(path)?, tileset, dialogues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::tileset as TilesetContent;
+
+    const TILESET: &str = "[atlas creatures]
+image = creatures.png
+tile_size = 16
+
+[sprite ghost]
+atlas = creatures
+tile = 0, 0
+";
+
+    // nine lines, the next one written after it is line 10
+    const PLAYER: &str = "[player]
+name = you
+tile = ghost
+color = FFFFFF
+health = 10
+attack = 2
+ai = player
+relationship = neutral
+depth = 0
+";
+
+    // left without any health
+    const GHOST: &str = "[ghost]
+name = ghost
+tile = ghost
+color = FF0000
+attack = 2
+ai = wander
+relationship = foe
+depth = 1
+";
+
+    fn get_error(text: &str) -> ContentError {
+        let tileset = Parser::parse("tileset.cfg", TILESET)
+            .and_then(|sections| TilesetContent::parse_tileset("tileset.cfg", &sections))
+            .unwrap();
+
+        Parser::parse("creatures.cfg", text)
+            .and_then(|sections| parse_creatures("creatures.cfg", &sections, &tileset, &[]))
+            .unwrap_err()
+    }
+
+    #[test]
+    fn bad_creatures_point_at_their_file_line_and_entry() {
+        let error = get_error(&format!("{}speed = 3\n", PLAYER));
+        assert_eq!(
+            error.to_string(),
+            "creatures.cfg:10: [player] unknown key 'speed'"
+        );
+
+        let error = get_error(&format!("{}{}", PLAYER, GHOST));
+        assert_eq!(error.file, "creatures.cfg");
+        assert_eq!(error.line, 10);
+        assert_eq!(error.entry.as_deref(), Some("ghost"));
+        assert_eq!(error.message, "missing 'health'");
+
+        let error = get_error(&format!("{}[ghost]\nname = ghost\n[ghost]\n", PLAYER));
+        assert_eq!(error.line, 12);
+        assert_eq!(error.entry.as_deref(), Some("ghost"));
+        assert_eq!(error.message, "already defined on line 10");
+
+        let error = get_error(&PLAYER.replace("color = FFFFFF", "color = white"));
+        assert_eq!(error.line, 4);
+        assert_eq!(error.entry.as_deref(), Some("player"));
+        assert_eq!(
+            error.message,
+            "'color' should be an RRGGBB colour, found 'white'"
+        );
+
+        let error = get_error(&PLAYER.replace("depth = 0", "depth = 2"));
+        assert_eq!(error.line, 9);
+        assert_eq!(error.message, "the player's depth should be 0, found 2");
+
+        let error = get_error(&format!("{}dialogue = hermit\n", PLAYER));
+        assert_eq!(error.line, 10);
+        assert_eq!(
+            error.message,
+            "only friendly creatures can talk, this one is 'neutral'"
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use crate::content::parser::{self as Parser, Entry, Section};
+use crate::content::ContentError;
+use crate::state::dialogue::GIFTS;
+use crate::state::domain::{
+    Condition, Dialogue, DialogueChoice, DialogueLine, Effect, EntityRelationship,
+};
+
+pub const DIALOGUES_FILE: &str = "./content/dialogues.cfg";
+
+const DIALOGUE_KEYS: [&str; 1] = ["start"];
+const LINE_KEYS: [&str; 2] = ["text", "choice"];
+const CHOICE_KEYS: [&str; 4] = ["text", "if", "do", "next"];
+
+// "has <item>", "lacks <item>", "hurt" or "unhurt"
+fn parse_condition(section: &Section, entry: &Entry) -> Result<Condition, ContentError> {
+    let (word, rest) = match entry.value.split_once(' ') {
+        Some((word, rest)) => (word, Some(rest.trim())),
+        None => (entry.value.as_str(), None),
+    };
+
+    match (word, rest) {
+        ("has", Some(item)) => Ok(Condition::Has(item.to_string())),
+        ("lacks", Some(item)) => Ok(Condition::Lacks(item.to_string())),
+        ("hurt", None) => Ok(Condition::Hurt),
+        ("unhurt", None) => Ok(Condition::Unhurt),
+        _ => Err(section.error(
+            entry.line,
+            format!(
+                "'if' should be has <item>, lacks <item>, hurt or unhurt, found '{}'",
+                entry.value
+            ),
+        )),
+    }
+}
+
+// "give <item>", "take <item>", "heal <amount>" or "become <relationship>"
+fn parse_effect(section: &Section, entry: &Entry) -> Result<Effect, ContentError> {
+    let (word, rest) = match entry.value.split_once(' ') {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (entry.value.as_str(), ""),
+    };
+
+    match (word, rest) {
+        ("give", item) if GIFTS.contains(&item) => Ok(Effect::Give(item.to_string())),
+        ("give", item) => Err(section.error(
+            entry.line,
+            format!(
+                "unknown item '{}', expected one of {}",
+                item,
+                GIFTS.join(", ")
+            ),
+        )),
+        ("take", item) if !item.is_empty() => Ok(Effect::Take(item.to_string())),
+        ("heal", amount) => match amount.parse::<i32>() {
+            Ok(amount) if amount > 0 => Ok(Effect::Heal(amount)),
+            _ => Err(section.error(
+                entry.line,
+                format!("heal should be followed by a number above zero, found '{}'", amount),
+            )),
+        },
+        ("become", "foe") => Ok(Effect::Become(EntityRelationship::Foe)),
+        ("become", "friendly") => Ok(Effect::Become(EntityRelationship::Friendly)),
+        ("become", "neutral") => Ok(Effect::Become(EntityRelationship::Neutral)),
+        _ => Err(section.error(
+            entry.line,
+            format!(
+                "'do' should be give <item>, take <item>, heal <amount> or become <foe, friendly or neutral>, found '{}'",
+                entry.value
+            ),
+        )),
+    }
+}
+
+fn parse_choice(section: &Section, lines: &[&str]) -> Result<DialogueChoice, ContentError> {
+    section.only_keys(&CHOICE_KEYS)?;
+
+    let next = match section.get("next") {
+        Some(entry) if lines.contains(&entry.value.as_str()) => Some(entry.value.to_owned()),
+        Some(entry) => {
+            return Err(section.error(entry.line, format!("unknown line '{}'", entry.value)))
+        }
+        None => None,
+    };
+
+    let mut conditions: Vec<Condition> = Vec::new();
+    for entry in section.get_all("if") {
+        conditions.push(parse_condition(section, entry)?);
+    }
+
+    let mut effects: Vec<Effect> = Vec::new();
+    for entry in section.get_all("do") {
+        effects.push(parse_effect(section, entry)?);
+    }
+
+    Ok(DialogueChoice {
+        text: section.require("text")?.value.to_owned(),
+        conditions,
+        effects,
+        next,
+    })
+}
+
+fn parse_line(
+    section: &Section,
+    id: &str,
+    choices: &[(&str, DialogueChoice)],
+) -> Result<DialogueLine, ContentError> {
+    section.only_keys(&LINE_KEYS)?;
+
+    let mut line_choices: Vec<DialogueChoice> = Vec::new();
+
+    for entry in section.get_all("choice") {
+        match choices.iter().find(|(name, _)| *name == entry.value) {
+            Some((_, choice)) => line_choices.push(choice.to_owned()),
+            None => {
+                return Err(section.error(entry.line, format!("unknown choice '{}'", entry.value)))
+            }
+        }
+    }
+
+    Ok(DialogueLine {
+        id: id.to_string(),
+        text: section.require("text")?.value.to_owned(),
+        choices: line_choices,
+    })
+}
+
+// every line that can come up once the dialogue started, the first one first
+fn parse_dialogue(
+    section: &Section,
+    id: &str,
+    lines: &[DialogueLine],
+) -> Result<Dialogue, ContentError> {
+    section.only_keys(&DIALOGUE_KEYS)?;
+
+    let start = section.require("start")?;
+    let first = match lines.iter().find(|line| line.id == start.value) {
+        Some(first) => first,
+        None => return Err(section.error(start.line, format!("unknown line '{}'", start.value))),
+    };
+
+    let mut reached: Vec<DialogueLine> = vec![first.to_owned()];
+    let mut index = 0;
+
+    while index < reached.len() {
+        let next: Vec<String> = reached[index]
+            .choices
+            .iter()
+            .filter_map(|choice| choice.next.to_owned())
+            .collect();
+
+        for id in next {
+            if !reached.iter().any(|line| line.id == id) {
+                reached.extend(lines.iter().find(|line| line.id == id).cloned());
+            }
+        }
+
+        index += 1;
+    }
+
+    Ok(Dialogue {
+        id: id.to_string(),
+        lines: reached,
+    })
+}
+
+pub fn parse_dialogues(sections: &[Section]) -> Result<Vec<Dialogue>, ContentError> {
+    // lines and choices point at each other, every name is known before anything is read
+    let line_names: Vec<&str> = sections
+        .iter()
+        .filter_map(|section| match section.kind() {
+            Some(("line", name)) => Some(name),
+            _ => None,
+        })
+        .collect();
+
+    let mut choices: Vec<(&str, DialogueChoice)> = Vec::new();
+    let mut lines: Vec<DialogueLine> = Vec::new();
+    let mut dialogues: Vec<Dialogue> = Vec::new();
+
+    for section in sections {
+        if let Some(("choice", name)) = section.kind() {
+            choices.push((name, parse_choice(section, &line_names)?));
+        }
+    }
+
+    for section in sections {
+        if let Some(("line", name)) = section.kind() {
+            lines.push(parse_line(section, name, &choices)?);
+        }
+    }
+
+    for section in sections {
+        match section.kind() {
+            Some(("dialogue", id)) => dialogues.push(parse_dialogue(section, id, &lines)?),
+            Some(("line" | "choice", _)) => (),
+            _ => {
+                return Err(section.error(
+                    section.line,
+                    "sections should be named [dialogue <id>], [line <name>] or [choice <name>]"
+                        .to_string(),
+                ))
+            }
+        }
+    }
+
+    Ok(dialogues)
+}
+
+pub fn load_dialogues(path: &str) -> Result<Vec<Dialogue>, ContentError> {
+    parse_dialogues(&Parser::read_file(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // eight lines, the next one written after it is line 9
+    const DIALOGUES: &str = "[dialogue hermit]
+start = hello
+[line hello]
+text = Hm?
+choice = bye
+[choice bye]
+text = Bye.
+do = become neutral
+";
+
+    fn get_error(text: &str) -> ContentError {
+        Parser::parse("dialogues.cfg", text)
+            .and_then(|sections| parse_dialogues(&sections))
+            .unwrap_err()
+    }
+
+    #[test]
+    fn bad_dialogues_point_at_their_file_line_and_entry() {
+        let error = get_error(&format!("{}mood = grumpy\n", DIALOGUES));
+        assert_eq!(
+            error.to_string(),
+            "dialogues.cfg:9: [choice bye] unknown key 'mood'"
+        );
+
+        let error = get_error(&DIALOGUES.replace("text = Hm?\n", ""));
+        assert_eq!(error.file, "dialogues.cfg");
+        assert_eq!(error.line, 3);
+        assert_eq!(error.entry.as_deref(), Some("line hello"));
+        assert_eq!(error.message, "missing 'text'");
+
+        let error = get_error(&format!("{}[line hello]\n", DIALOGUES));
+        assert_eq!(error.line, 9);
+        assert_eq!(error.entry.as_deref(), Some("line hello"));
+        assert_eq!(error.message, "already defined on line 3");
+
+        let error = get_error(&format!("{}next = goodbye\n", DIALOGUES));
+        assert_eq!(error.line, 9);
+        assert_eq!(error.entry.as_deref(), Some("choice bye"));
+        assert_eq!(error.message, "unknown line 'goodbye'");
+
+        let error = get_error(&DIALOGUES.replace("become neutral", "become angry"));
+        assert_eq!(error.line, 8);
+        assert!(error.message.ends_with("found 'become angry'"));
+    }
+}
@@ -0,0 +1,36 @@
+use std::fmt;
+
+pub mod parser;
+
+pub mod creatures;
+
+pub mod tileset;
+
+pub mod dialogues;
+
+pub mod watch;
+
+// Everything that can go wrong while reading a content file, pointing at the offending entry
+// so whoever is editing the file doesn't have to guess
+#[derive(Debug, Clone)]
+pub struct ContentError {
+    pub file: String,
+    pub line: usize,
+    pub entry: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ContentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // errors about the file as a whole don't point at any line
+        match self.line {
+            0 => write!(f, "{}: ", self.file)?,
+            line => write!(f, "{}:{}: ", self.file, line)?,
+        }
+
+        match &self.entry {
+            Some(entry) => write!(f, "[{}] {}", entry, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::color::Color;
+use crate::content::ContentError;
+use std::fmt::Debug;
+use std::fs;
+use std::str::FromStr;
+
+// Content files are made of sections holding "key = value" lines:
+//
+// # comments start with a hash
+// [ghost]
+// name = ghost
+// health = 6
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub key: String,
+    pub value: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub file: String,
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<Entry>,
+}
+
+pub fn read_file(path: &str) -> Result<Vec<Section>, ContentError> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse(path, &text),
+        Err(error) => Err(ContentError {
+            file: path.to_string(),
+            line: 0,
+            entry: None,
+            message: format!("could not read file: {}", error),
+        }),
+    }
+}
+
+pub fn parse(file: &str, text: &str) -> Result<Vec<Section>, ContentError> {
+    let mut sections: Vec<Section> = Vec::new();
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = raw_line.trim();
+        let line_number = index + 1;
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_string();
+
+            if let Some(existing) = sections.iter().find(|section| section.name == name) {
+                return Err(ContentError {
+                    file: file.to_string(),
+                    line: line_number,
+                    entry: Some(name),
+                    message: format!("already defined on line {}", existing.line),
+                });
+            }
+
+            sections.push(Section {
+                file: file.to_string(),
+                name,
+                line: line_number,
+                entries: Vec::new(),
+            });
+
+            continue;
+        }
+
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => {
+                return Err(ContentError {
+                    file: file.to_string(),
+                    line: line_number,
+                    entry: sections.last().map(|section| section.name.to_owned()),
+                    message: format!("expected 'key = value', found '{}'", line),
+                })
+            }
+        };
+
+        match sections.last_mut() {
+            Some(section) => section.entries.push(Entry {
+                key: key.to_string(),
+                value: value.to_string(),
+                line: line_number,
+            }),
+            None => {
+                return Err(ContentError {
+                    file: file.to_string(),
+                    line: line_number,
+                    entry: None,
+                    message: format!("'{}' is outside of any [section]", key),
+                })
+            }
+        }
+    }
+
+    Ok(sections)
+}
+
+impl Section {
+    pub fn error(&self, line: usize, message: String) -> ContentError {
+        ContentError {
+            file: self.file.to_owned(),
+            line,
+            entry: Some(self.name.to_owned()),
+            message,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().rev().find(|entry| entry.key == key)
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.key == key)
+            .collect()
+    }
+
+    // "[sprite grass]" is a section of kind "sprite" named "grass"
+    pub fn kind(&self) -> Option<(&str, &str)> {
+        self.name
+            .split_once(' ')
+            .map(|(kind, name)| (kind.trim(), name.trim()))
+    }
+
+    pub fn require(&self, key: &str) -> Result<&Entry, ContentError> {
+        match self.get(key) {
+            Some(entry) => Ok(entry),
+            None => Err(self.error(self.line, format!("missing '{}'", key))),
+        }
+    }
+
+    // typos in keys are silently ignored otherwise, so anything unknown is an error
+    pub fn only_keys(&self, known: &[&str]) -> Result<(), ContentError> {
+        match self
+            .entries
+            .iter()
+            .find(|entry| !known.contains(&entry.key.as_str()))
+        {
+            Some(entry) => Err(self.error(entry.line, format!("unknown key '{}'", entry.key))),
+            None => Ok(()),
+        }
+    }
+
+    pub fn require_int(&self, key: &str) -> Result<i32, ContentError> {
+        let entry = self.require(key)?;
+
+        entry.value.parse().map_err(|_| {
+            self.error(
+                entry.line,
+                format!("'{}' should be a number, found '{}'", key, entry.value),
+            )
+        })
+    }
+
+    pub fn require_color(&self, key: &str) -> Result<Color, ContentError> {
+        let entry = self.require(key)?;
+        let hex = entry.value.trim_start_matches('#');
+
+        match hex.len() == 6 {
+            true => Color::from_hex(hex).map_err(|_| {
+                self.error(
+                    entry.line,
+                    format!(
+                        "'{}' should be an RRGGBB colour, found '{}'",
+                        key, entry.value
+                    ),
+                )
+            }),
+            false => Err(self.error(
+                entry.line,
+                format!(
+                    "'{}' should be an RRGGBB colour, found '{}'",
+                    key, entry.value
+                ),
+            )),
+        }
+    }
+
+    pub fn require_parsed<T: FromStr>(&self, key: &str) -> Result<T, ContentError> {
+        let entry = self.require(key)?;
+
+        entry
+            .value
+            .parse()
+            .map_err(|_| self.error(entry.line, format!("invalid '{}': '{}'", key, entry.value)))
+    }
+
+    pub fn require_named<T: Copy + Debug>(
+        &self,
+        key: &str,
+        options: &[T],
+    ) -> Result<T, ContentError> {
+        self.named(self.require(key)?, options)
+    }
+
+    // enums are written with their debug names
+    pub fn named<T: Copy + Debug>(&self, entry: &Entry, options: &[T]) -> Result<T, ContentError> {
+        options
+            .iter()
+            .find(|option| format!("{:?}", option) == entry.value)
+            .copied()
+            .ok_or_else(|| {
+                self.error(
+                    entry.line,
+                    format!("invalid '{}': '{}'", entry.key, entry.value),
+                )
+            })
+    }
+}
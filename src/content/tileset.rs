@@ -0,0 +1,369 @@
+use crate::content::parser::{self as Parser, Section};
+use crate::content::ContentError;
+use std::collections::HashMap;
+
+pub const TILESET_FILE: &str = "./content/tileset.cfg";
+
+const ATLAS_KEYS: [&str; 2] = ["image", "tile_size"];
+const SPRITE_KEYS: [&str; 3] = ["atlas", "tile", "glyph"];
+
+// what text renderers show for sprites that do not pick a glyph
+const DEFAULT_GLYPH: char = '?';
+
+// sprites the game picks by name for terrain, doors, items and traps (see helpers::gen_terrain
+// and traps::get_face). Creature faces are checked by the creature loader instead
+const GAME_SPRITES: [&str; 16] = [
+    "dirt",
+    "grass",
+    "brick_wall",
+    "water_shallow",
+    "water_deep",
+    "lava",
+    "rubble",
+    "door_closed",
+    "door_open",
+    "door_locked",
+    "key",
+    "pickaxe",
+    "trap_spike",
+    "trap_teleport",
+    "trap_alarm",
+    "trap_pit",
+];
+
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    pub id: String,
+    pub image: String,
+    pub tile_size: i32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteVariant {
+    pub x: i32,
+    pub y: i32,
+    pub weight: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    pub atlas: String,
+    pub variants: Vec<SpriteVariant>,
+    pub glyph: char,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tileset {
+    pub atlases: Vec<Atlas>,
+    pub sprites: HashMap<String, Sprite>,
+}
+
+impl Sprite {
+    // entities keep a random roll instead of a variant index, so the sprite they end up with
+    // only depends on the weights in the descriptor file
+    pub fn variant(&self, roll: u32) -> &SpriteVariant {
+        // can't overflow, parse_sprite refuses weights that add up to more than a u32
+        let total: u32 = self.variants.iter().map(|variant| variant.weight).sum();
+        let mut remaining = roll % total.max(1);
+
+        for variant in self.variants.iter() {
+            if remaining < variant.weight {
+                return variant;
+            }
+
+            remaining -= variant.weight;
+        }
+
+        &self.variants[0]
+    }
+}
+
+impl Tileset {
+    pub fn atlas(&self, id: &str) -> Option<&Atlas> {
+        self.atlases.iter().find(|atlas| atlas.id == id)
+    }
+
+    pub fn has_sprite(&self, name: &str) -> bool {
+        self.sprites.contains_key(name)
+    }
+
+    pub fn glyph(&self, name: &str) -> char {
+        self.sprites
+            .get(name)
+            .map(|sprite| sprite.glyph)
+            .unwrap_or(DEFAULT_GLYPH)
+    }
+}
+
+fn parse_atlas(section: &Section, id: &str) -> Result<Atlas, ContentError> {
+    section.only_keys(&ATLAS_KEYS)?;
+
+    let tile_size = section.require_int("tile_size")?;
+    if tile_size <= 0 {
+        return Err(section.error(
+            section.require("tile_size")?.line,
+            format!("tile_size should be above zero, found {}", tile_size),
+        ));
+    }
+
+    Ok(Atlas {
+        id: id.to_string(),
+        image: section.require("image")?.value.to_owned(),
+        tile_size,
+    })
+}
+
+// "x, y" or "x, y, weight", weight defaulting to 1
+fn parse_variant(
+    section: &Section,
+    value: &str,
+    line: usize,
+) -> Result<SpriteVariant, ContentError> {
+    let invalid = || {
+        section.error(
+            line,
+            format!(
+                "'tile' should be 'x, y' or 'x, y, weight', found '{}'",
+                value
+            ),
+        )
+    };
+    let coordinate = |number: &str| number.parse::<i32>().ok().filter(|number| *number >= 0);
+
+    let parts: Vec<&str> = value.split(',').map(|part| part.trim()).collect();
+    let (x, y, weight) = match parts.as_slice() {
+        [x, y] => (*x, *y, "1"),
+        [x, y, weight] => (*x, *y, *weight),
+        _ => return Err(invalid()),
+    };
+
+    let (x, y) = match (coordinate(x), coordinate(y)) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(invalid()),
+    };
+
+    match weight.parse::<u32>() {
+        Ok(weight) if weight > 0 => Ok(SpriteVariant { x, y, weight }),
+        _ => Err(section.error(
+            line,
+            format!(
+                "the weight should be a number above zero, found '{}'",
+                weight
+            ),
+        )),
+    }
+}
+
+fn parse_sprite(section: &Section, atlases: &[Atlas]) -> Result<Sprite, ContentError> {
+    section.only_keys(&SPRITE_KEYS)?;
+
+    let atlas = section.require("atlas")?;
+    if !atlases.iter().any(|known| known.id == atlas.value) {
+        return Err(section.error(atlas.line, format!("unknown atlas '{}'", atlas.value)));
+    }
+
+    let mut variants: Vec<SpriteVariant> = Vec::new();
+    let mut total: u32 = 0;
+
+    // Sprite::variant adds the weights up, they have to fit
+    for entry in section.get_all("tile") {
+        let variant = parse_variant(section, &entry.value, entry.line)?;
+
+        total = total.checked_add(variant.weight).ok_or_else(|| {
+            section.error(
+                entry.line,
+                format!("the weights add up to more than {}", u32::MAX),
+            )
+        })?;
+        variants.push(variant);
+    }
+
+    if variants.is_empty() {
+        return Err(section.error(section.line, "missing 'tile'".to_string()));
+    }
+
+    let glyph = match section.get("glyph") {
+        Some(entry) => {
+            let mut chars = entry.value.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(glyph), None) => glyph,
+                _ => {
+                    return Err(section.error(
+                        entry.line,
+                        format!(
+                            "'glyph' should be a single character, found '{}'",
+                            entry.value
+                        ),
+                    ))
+                }
+            }
+        }
+        None => DEFAULT_GLYPH,
+    };
+
+    Ok(Sprite {
+        atlas: atlas.value.to_owned(),
+        variants,
+        glyph,
+    })
+}
+
+pub fn parse_tileset(file: &str, sections: &[Section]) -> Result<Tileset, ContentError> {
+    let mut atlases: Vec<Atlas> = Vec::new();
+    let mut sprites: HashMap<String, Sprite> = HashMap::new();
+
+    // atlases first, so sprites can point at atlases defined further down the file
+    for section in sections {
+        if let Some(("atlas", id)) = section.kind() {
+            atlases.push(parse_atlas(section, id)?);
+        }
+    }
+
+    for section in sections {
+        match section.kind() {
+            Some(("atlas", _)) => (),
+            Some(("sprite", name)) => {
+                sprites.insert(name.to_string(), parse_sprite(section, &atlases)?);
+            }
+            _ => {
+                return Err(section.error(
+                    section.line,
+                    "sections should be named [atlas <id>] or [sprite <name>]".to_string(),
+                ))
+            }
+        }
+    }
+
+    if atlases.is_empty() {
+        return Err(ContentError {
+            file: file.to_string(),
+            line: 0,
+            entry: None,
+            message: "no [atlas <id>] defined".to_string(),
+        });
+    }
+
+    Ok(Tileset { atlases, sprites })
+}
+
+fn check_game_sprites(file: &str, tileset: &Tileset) -> Result<(), ContentError> {
+    match GAME_SPRITES.iter().find(|name| !tileset.has_sprite(name)) {
+        Some(name) => Err(ContentError {
+            file: file.to_string(),
+            line: 0,
+            entry: None,
+            message: format!("no [sprite {}] defined, the game draws it itself", name),
+        }),
+        None => Ok(()),
+    }
+}
+
+pub fn load_tileset(path: &str) -> Result<Tileset, ContentError> {
+    let tileset = parse_tileset(path, &Parser::read_file(path)?)?;
+    check_game_sprites(path, &tileset)?;
+    Ok(tileset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // six lines, the next one written after it is line 7
+    const TILESET: &str = "[atlas terrain]
+image = terrain.png
+tile_size = 16
+[sprite grass]
+atlas = terrain
+tile = 0, 0
+";
+
+    fn get_error(text: &str) -> ContentError {
+        Parser::parse("tileset.cfg", text)
+            .and_then(|sections| parse_tileset("tileset.cfg", &sections))
+            .unwrap_err()
+    }
+
+    #[test]
+    fn bad_sprites_point_at_their_file_line_and_entry() {
+        let error = get_error(&format!("{}colour = green\n", TILESET));
+        assert_eq!(
+            error.to_string(),
+            "tileset.cfg:7: [sprite grass] unknown key 'colour'"
+        );
+
+        let error = get_error(&format!("{}[sprite dirt]\natlas = terrain\n", TILESET));
+        assert_eq!(error.file, "tileset.cfg");
+        assert_eq!(error.line, 7);
+        assert_eq!(error.entry.as_deref(), Some("sprite dirt"));
+        assert_eq!(error.message, "missing 'tile'");
+
+        let error = get_error(&format!("{}[sprite grass]\n", TILESET));
+        assert_eq!(error.line, 7);
+        assert_eq!(error.entry.as_deref(), Some("sprite grass"));
+        assert_eq!(error.message, "already defined on line 4");
+
+        let error = get_error(&format!("{}tile = 1, -2\n", TILESET));
+        assert_eq!(error.line, 7);
+        assert_eq!(
+            error.message,
+            "'tile' should be 'x, y' or 'x, y, weight', found '1, -2'"
+        );
+
+        let error = get_error(&format!("{}glyph = ..\n", TILESET));
+        assert_eq!(error.line, 7);
+        assert_eq!(
+            error.message,
+            "'glyph' should be a single character, found '..'"
+        );
+    }
+
+    #[test]
+    fn weights_are_whole_numbers_above_zero_that_fit_together() {
+        let error = get_error(&format!("{}tile = 1, 0, 0\n", TILESET));
+        assert_eq!(error.line, 7);
+        assert_eq!(error.entry.as_deref(), Some("sprite grass"));
+        assert_eq!(
+            error.message,
+            "the weight should be a number above zero, found '0'"
+        );
+
+        let error = get_error(&format!("{}tile = 1, 0, -3\n", TILESET));
+        assert_eq!(error.line, 7);
+        assert_eq!(
+            error.message,
+            "the weight should be a number above zero, found '-3'"
+        );
+
+        let error = get_error(&format!(
+            "{}tile = 1, 0, 4294967295\ntile = 2, 0, 4294967295\n",
+            TILESET
+        ));
+        assert_eq!(error.line, 7);
+        assert_eq!(error.message, "the weights add up to more than 4294967295");
+
+        let tileset = Parser::parse("tileset.cfg", &format!("{}tile = 1, 0, 3\n", TILESET))
+            .and_then(|sections| parse_tileset("tileset.cfg", &sections))
+            .unwrap();
+        let grass = &tileset.sprites["grass"];
+
+        // the first tile has weight 1 (the default), the second 3
+        let picked: Vec<i32> = (0..8).map(|roll| grass.variant(roll).x).collect();
+        assert_eq!(picked, vec![0, 1, 1, 1, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn the_sprites_the_game_draws_itself_are_required() {
+        let tileset = Parser::parse("tileset.cfg", TILESET)
+            .and_then(|sections| parse_tileset("tileset.cfg", &sections))
+            .unwrap();
+        let error = check_game_sprites("tileset.cfg", &tileset).unwrap_err();
+        assert_eq!(error.line, 0);
+        assert_eq!(
+            error.message,
+            "no [sprite dirt] defined, the game draws it itself"
+        );
+
+        assert!(load_tileset(TILESET_FILE).is_ok());
+    }
+}
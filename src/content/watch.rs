@@ -0,0 +1,51 @@
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+// Polls the modification time of content files so they can be reloaded while playing,
+// checking at most once in a while since this runs every frame
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct ContentWatcher {
+    files: Vec<(String, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+fn modified_at(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl ContentWatcher {
+    pub fn new(paths: &[&str]) -> ContentWatcher {
+        ContentWatcher {
+            files: paths
+                .iter()
+                .map(|path| (path.to_string(), modified_at(path)))
+                .collect(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // files that changed since the last time they were seen
+    pub fn poll(&mut self) -> Vec<String> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+
+        self.last_poll = Instant::now();
+
+        let mut changed: Vec<String> = Vec::new();
+
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = modified_at(path);
+
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed.push(path.to_owned());
+            }
+        }
+
+        changed
+    }
+}
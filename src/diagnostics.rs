@@ -0,0 +1,318 @@
+use std::env;
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Leveled, structured logging written to a rotating file, so a run can be diagnosed after
+// the fact. Configured through environment variables:
+//
+// POORGUELIKE_LOG           level for everything and/or per target, ex: "info,ai=debug,map=trace"
+// POORGUELIKE_LOG_FILE      where to write, defaults to "poorguelike.log"
+// POORGUELIKE_LOG_MAX_BYTES size that triggers a rotation, defaults to 1MB
+//
+// Each line looks like: ts=1700000000.123 level=DEBUG target=ai msg="entity moved" id=... side=LEFT
+
+pub const TARGET_AI: &str = "ai";
+pub const TARGET_MAP: &str = "map";
+pub const TARGET_INPUT: &str = "input";
+pub const TARGET_GAME: &str = "game";
+
+const DEFAULT_FILE: &str = "poorguelike.log";
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024;
+const ROTATED_FILES: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn from_name(name: &str) -> Option<Level> {
+        match name.trim().to_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub default_level: Level,
+    pub target_levels: Vec<(String, Level)>,
+    pub path: String,
+    pub max_bytes: u64,
+}
+
+struct Logger {
+    config: LogConfig,
+    file: Option<File>,
+    written: u64,
+    debug_override: bool,
+}
+
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+// parses "info,ai=debug" style filters, returning the bad pieces so they can be reported
+fn parse_filter(filter: &str, config: &mut LogConfig) -> Vec<String> {
+    let mut invalid: Vec<String> = Vec::new();
+
+    for piece in filter.split(',').filter(|piece| !piece.trim().is_empty()) {
+        match piece.split_once('=') {
+            Some((target, level)) => match Level::from_name(level) {
+                Some(level) => config
+                    .target_levels
+                    .push((target.trim().to_lowercase(), level)),
+                None => invalid.push(piece.to_string()),
+            },
+            None => match Level::from_name(piece) {
+                Some(level) => config.default_level = level,
+                None => invalid.push(piece.to_string()),
+            },
+        }
+    }
+
+    invalid
+}
+
+pub fn init_from_env() {
+    let mut config = LogConfig {
+        default_level: Level::Info,
+        target_levels: Vec::new(),
+        path: env::var("POORGUELIKE_LOG_FILE").unwrap_or_else(|_| DEFAULT_FILE.to_string()),
+        max_bytes: env::var("POORGUELIKE_LOG_MAX_BYTES")
+            .ok()
+            .and_then(|bytes| bytes.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BYTES),
+    };
+
+    let invalid = match env::var("POORGUELIKE_LOG") {
+        Ok(filter) => parse_filter(&filter, &mut config),
+        Err(_) => Vec::new(),
+    };
+
+    init(config);
+
+    for piece in invalid {
+        log(
+            Level::Warn,
+            TARGET_GAME,
+            "ignoring invalid POORGUELIKE_LOG entry",
+            &[("entry", &piece)],
+        );
+    }
+}
+
+pub fn init(config: LogConfig) {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.path);
+
+    let (file, written) = match file {
+        Ok(file) => {
+            let written = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+            (Some(file), written)
+        }
+        Err(error) => {
+            eprintln!("could not open log file {}: {}", config.path, error);
+            (None, 0)
+        }
+    };
+
+    *LOGGER.lock().unwrap() = Some(Logger {
+        config,
+        file,
+        written,
+        debug_override: false,
+    });
+}
+
+// the in-game debug toggle bumps every target to at least debug level
+pub fn set_debug(enabled: bool) {
+    if let Some(logger) = LOGGER.lock().unwrap().as_mut() {
+        logger.debug_override = enabled;
+    }
+}
+
+fn target_level(logger: &Logger, target: &str) -> Level {
+    let configured = logger
+        .config
+        .target_levels
+        .iter()
+        .rev()
+        .find(|(name, _)| name == target)
+        .map(|(_, level)| *level)
+        .unwrap_or(logger.config.default_level);
+
+    match logger.debug_override {
+        true => configured.max(Level::Debug),
+        false => configured,
+    }
+}
+
+pub fn enabled(level: Level, target: &str) -> bool {
+    match LOGGER.lock().unwrap().as_ref() {
+        Some(logger) => level <= target_level(logger, target),
+        None => false,
+    }
+}
+
+fn rotate(logger: &mut Logger) {
+    logger.file = None;
+
+    let path = &logger.config.path;
+
+    for n in (1..ROTATED_FILES).rev() {
+        let _ = fs::rename(format!("{}.{}", path, n), format!("{}.{}", path, n + 1));
+    }
+    let _ = fs::rename(path, format!("{}.1", path));
+
+    logger.file = File::create(path).ok();
+    logger.written = 0;
+}
+
+fn format_value(value: &str) -> String {
+    match value.contains(' ') || value.contains('"') || value.contains('\n') || value.is_empty() {
+        true => format!("{:?}", value),
+        false => value.to_string(),
+    }
+}
+
+pub fn log(level: Level, target: &str, message: &str, fields: &[(&str, &dyn Display)]) {
+    let mut guard = LOGGER.lock().unwrap();
+
+    let logger = match guard.as_mut() {
+        Some(logger) => logger,
+        None => return,
+    };
+
+    if level > target_level(logger, target) {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs_f64())
+        .unwrap_or(0.0);
+
+    let mut line = format!(
+        "ts={:.3} level={} target={} msg={:?}",
+        timestamp,
+        level.name(),
+        target,
+        message
+    );
+
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={}", key, format_value(&value.to_string())));
+    }
+
+    line.push('\n');
+
+    if level <= Level::Warn {
+        eprint!("{}", line);
+    }
+
+    if logger.written + line.len() as u64 > logger.config.max_bytes {
+        rotate(logger);
+    }
+
+    if let Some(file) = logger.file.as_mut() {
+        if file.write_all(line.as_bytes()).is_ok() {
+            logger.written += line.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn get_config(path: &str) -> LogConfig {
+        LogConfig {
+            default_level: Level::Info,
+            target_levels: Vec::new(),
+            path: path.to_string(),
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+
+    #[test]
+    fn filters_set_levels_per_target_and_report_what_they_cant_read() {
+        let mut config = get_config(DEFAULT_FILE);
+        let invalid = parse_filter("warn, ai=Trace,map=loud,,chatty", &mut config);
+
+        assert_eq!(invalid, vec!["map=loud".to_string(), "chatty".to_string()]);
+
+        let mut logger = Logger {
+            config,
+            file: None,
+            written: 0,
+            debug_override: false,
+        };
+        assert_eq!(target_level(&logger, TARGET_AI), Level::Trace);
+        assert_eq!(target_level(&logger, TARGET_MAP), Level::Warn);
+
+        // the debug toggle raises the quiet targets without lowering the verbose ones
+        logger.debug_override = true;
+        assert_eq!(target_level(&logger, TARGET_AI), Level::Trace);
+        assert_eq!(target_level(&logger, TARGET_MAP), Level::Debug);
+
+        assert_eq!(format_value("LEFT"), "LEFT");
+        assert_eq!(format_value("entity moved"), "\"entity moved\"");
+        assert_eq!(format_value(""), "\"\"");
+        assert_eq!(format_value("two\nlines"), "\"two\\nlines\"");
+    }
+
+    #[test]
+    fn rotating_shifts_the_older_files_along() {
+        let dir = env::temp_dir().join(format!("poorguelike-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.log");
+        let path = path.to_str().unwrap();
+
+        let mut logger = Logger {
+            config: get_config(path),
+            file: None,
+            written: 10,
+            debug_override: false,
+        };
+
+        for generation in 1..=(ROTATED_FILES + 1) {
+            fs::write(path, generation.to_string()).unwrap();
+            rotate(&mut logger);
+        }
+
+        let rotated: Vec<String> = (1..=ROTATED_FILES + 1)
+            .map(|n| fs::read_to_string(format!("{}.{}", path, n)).unwrap_or_default())
+            .collect();
+        let current = fs::read_to_string(path);
+        let _ = fs::remove_dir_all(&dir);
+
+        // the oldest one is dropped, and logging starts over in an empty file
+        assert_eq!(rotated, vec!["4", "3", "2", ""]);
+        assert_eq!(current.unwrap(), "");
+        assert_eq!(logger.written, 0);
+    }
+}
@@ -1,240 +1,853 @@
-use crate::state::domain::{EntityMode, Game, Position, Settings, TilePosition};
+use crate::animation::Animator;
+use crate::app::MenuItem;
+use crate::color::Color;
+use crate::content::tileset::Tileset;
+use crate::effects::{EffectKind, Effects};
+use crate::render as Render;
+use crate::render::{DrawItem, DrawList, Layer, PaletteColors, Renderer};
+use crate::state::core as StateCore;
+use crate::state::dialogue as Talking;
+use crate::state::domain::{EntityMode, Game, Hud, MessageKind, Position, Settings};
 use crate::state::helpers as StateHelpers;
-use crate::HashMap;
-use raylib::color::Color;
 use raylib::drawing::RaylibDrawHandle;
+use raylib::ffi;
 use raylib::math::{Rectangle, Vector2};
 use raylib::misc::AsF32;
-use raylib::prelude::{RaylibDraw, Texture2D};
-
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
-pub enum TileNames {
-    Dirt1,
-    Dirt2,
-    Dirt3,
-    Dirt4,
-    Dirt0,
-    Grass1,
-    Grass2,
-    Grass3,
-    BrickWall1,
-    BrickWall2,
-    BrickWall3,
-    NakedPlayer,
-    NPC,
-    Outline,
-}
-
-pub fn get_tilenames() -> HashMap<TileNames, TilePosition> {
-    let mut tile_map: HashMap<TileNames, TilePosition> = HashMap::new();
-
-    tile_map.insert(TileNames::Dirt0, TilePosition { x: 0.0, y: 0.0 });
-    tile_map.insert(TileNames::Dirt1, TilePosition { x: 1.0, y: 0.0 });
-    tile_map.insert(TileNames::Dirt2, TilePosition { x: 2.0, y: 0.0 });
-    tile_map.insert(TileNames::Dirt3, TilePosition { x: 3.0, y: 0.0 });
-    tile_map.insert(TileNames::Dirt4, TilePosition { x: 4.0, y: 0.0 });
-    tile_map.insert(TileNames::Grass1, TilePosition { x: 5.0, y: 0.0 });
-    tile_map.insert(TileNames::Grass2, TilePosition { x: 6.0, y: 0.0 });
-    tile_map.insert(TileNames::Grass3, TilePosition { x: 7.0, y: 0.0 });
-    tile_map.insert(TileNames::BrickWall1, TilePosition { x: 7.0, y: 15.0 });
-    tile_map.insert(TileNames::BrickWall2, TilePosition { x: 6.0, y: 15.0 });
-    tile_map.insert(TileNames::BrickWall3, TilePosition { x: 6.0, y: 13.0 });
-
-    tile_map.insert(TileNames::NakedPlayer, TilePosition { x: 25.0, y: 0.0 });
-    tile_map.insert(TileNames::NPC, TilePosition { x: 25.0, y: 9.0 });
-    tile_map.insert(TileNames::Outline, TilePosition { x: 24.0, y: 7.0 });
-
-    tile_map
-}
-
-pub fn grid(d: &mut RaylibDrawHandle, settings: &Settings) {
-    for n in 0..settings.graphic.columns {
-        d.draw_line(
-            n * settings.graphic.tile_width,
-            0,
-            n * settings.graphic.tile_width,
-            settings.window.height,
-            Color::from_hex("222222").unwrap(),
+use raylib::prelude::{
+    RaylibDraw, RaylibHandle, RaylibTextureModeExt, RaylibThread, RenderTexture2D, Texture2D,
+};
+use raylib::text::measure_text;
+use std::collections::HashMap;
+
+// the game keeps its own colours so the core doesn't need raylib, they are handed over here
+impl From<Color> for ffi::Color {
+    fn from(color: Color) -> ffi::Color {
+        ffi::Color {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: color.a,
+        }
+    }
+}
+
+// loaded textures, by atlas id
+pub type Textures = HashMap<String, Texture2D>;
+
+pub fn load_textures(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    tileset: &Tileset,
+) -> Result<Textures, String> {
+    let mut textures: Textures = HashMap::new();
+
+    for atlas in tileset.atlases.iter() {
+        let texture = rl
+            .load_texture(thread, &atlas.image)
+            .map_err(|error| format!("atlas '{}': {}", atlas.id, error))?;
+
+        textures.insert(atlas.id.to_owned(), texture);
+    }
+
+    Ok(textures)
+}
+
+// Where tiles land on screen, everything that draws or picks tiles goes through this so the
+// map always lines up with whatever window size, rows and columns the settings ask for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileTransform {
+    pub tile_width: f32,
+    pub tile_height: f32,
+    pub columns: i32,
+    pub rows: i32,
+}
+
+impl TileTransform {
+    pub fn from_settings(settings: &Settings) -> TileTransform {
+        TileTransform {
+            tile_width: settings.graphic.tile_width.as_f32(),
+            tile_height: settings.graphic.tile_height.as_f32(),
+            columns: settings.graphic.columns,
+            rows: settings.graphic.rows,
+        }
+    }
+
+    pub fn map_width(&self) -> f32 {
+        self.tile_width * self.columns.as_f32()
+    }
+
+    pub fn map_height(&self) -> f32 {
+        self.tile_height * self.rows.as_f32()
+    }
+
+    pub fn tile_to_screen(&self, tile_pos: &Position) -> Vector2 {
+        Vector2::new(
+            tile_pos.x.as_f32() * self.tile_width,
+            tile_pos.y.as_f32() * self.tile_height,
+        )
+    }
+
+    pub fn tile_rect(&self, tile_pos: &Position) -> Rectangle {
+        let corner = self.tile_to_screen(tile_pos);
+
+        Rectangle::new(corner.x, corner.y, self.tile_width, self.tile_height)
+    }
+
+    pub fn screen_to_tile(&self, x: i32, y: i32) -> Option<Position> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+
+        let tile_pos = Position {
+            x: (x.as_f32() / self.tile_width) as i32,
+            y: (y.as_f32() / self.tile_height) as i32,
+        };
+
+        match tile_pos.x < self.columns && tile_pos.y < self.rows {
+            true => Some(tile_pos),
+            false => None,
+        }
+    }
+}
+
+pub fn grid(d: &mut impl RaylibDraw, settings: &Settings) {
+    let transform = TileTransform::from_settings(settings);
+    let color = Render::palette_colors(settings.palette).grid;
+
+    for n in 0..=transform.columns {
+        let x = n.as_f32() * transform.tile_width;
+
+        d.draw_line_v(
+            Vector2::new(x, 0.0),
+            Vector2::new(x, transform.map_height()),
+            color,
         );
     }
 
-    for n in 0..settings.graphic.rows {
-        d.draw_line(
-            0,
-            n * settings.graphic.tile_height,
-            settings.window.width,
-            n * settings.graphic.tile_height,
-            Color::from_hex("222222").unwrap(),
+    for n in 0..=transform.rows {
+        let y = n.as_f32() * transform.tile_height;
+
+        d.draw_line_v(
+            Vector2::new(0.0, y),
+            Vector2::new(transform.map_width(), y),
+            color,
         );
     }
 }
 
-pub fn entities(
-    d: &mut RaylibDrawHandle,
-    game: &Game,
-    settings: &Settings,
-    tileset_terrain: &Texture2D,
-    tileset_entities: &Texture2D,
+pub fn sprite(
+    d: &mut impl RaylibDraw,
+    transform: &TileTransform,
+    tileset: &Tileset,
+    textures: &Textures,
+    item: &DrawItem,
 ) {
-    let mut tile_map: HashMap<TileNames, TilePosition> = get_tilenames();
+    let sprite = match tileset.sprites.get(item.sprite) {
+        Some(sprite) => sprite,
+        None => return,
+    };
 
-    let tile_side_size = 16.0;
-    let scale = 2.5;
+    let (atlas, texture) = match (tileset.atlas(&sprite.atlas), textures.get(&sprite.atlas)) {
+        (Some(atlas), Some(texture)) => (atlas, texture),
+        _ => return,
+    };
 
-    for building in game.buildings.iter() {
-        let source_rec = Rectangle::new(
-            tile_side_size * tile_map.get(&building.characteristics.face).unwrap().x,
-            tile_side_size * tile_map.get(&building.characteristics.face).unwrap().y,
-            16.0,
-            16.0,
-        );
-        let dest_rec = Rectangle::new(
-            building.pos.x.as_f32() * (tile_side_size * scale),
-            building.pos.y.as_f32() * (tile_side_size * scale),
-            tile_side_size * scale,
-            tile_side_size * scale,
-        );
-        let origin = Vector2::new(0.0, 0.0);
-        d.draw_texture_tiled(
-            tileset_terrain,
-            source_rec,
-            dest_rec,
-            origin,
-            0.0,
-            scale,
-            match building.mode {
-                EntityMode::BrickWall => Color::from_hex("CCCCCC").unwrap(),
-                EntityMode::Ground => Color::from_hex("AAAAAA").unwrap(),
-                _ => unreachable!(),
-            },
-        );
+    let variant = sprite.variant(item.variant);
+    let tile_side_size = atlas.tile_size.as_f32();
+
+    let source_rec = Rectangle::new(
+        tile_side_size * variant.x.as_f32(),
+        tile_side_size * variant.y.as_f32(),
+        tile_side_size,
+        tile_side_size,
+    );
+    let mut dest_rec = transform.tile_rect(&item.pos);
+    dest_rec.x += item.offset_x * transform.tile_width;
+    dest_rec.y += item.offset_y * transform.tile_height;
+    let origin = Vector2::new(0.0, 0.0);
+    d.draw_texture_pro(texture, source_rec, dest_rec, origin, 0.0, item.color);
+}
+
+// Terrain barely ever changes, so it is drawn once into an off-screen texture and that
+// texture is drawn every frame instead of one sprite per tile
+pub struct TerrainCache {
+    texture: Option<RenderTexture2D>,
+    revision: u32,
+    // how many tiles were explored when it was drawn, unexplored ones are left out
+    explored: usize,
+    transform: Option<TileTransform>,
+}
+
+impl TerrainCache {
+    pub fn new() -> TerrainCache {
+        TerrainCache {
+            texture: None,
+            revision: 0,
+            explored: 0,
+            transform: None,
+        }
     }
+}
 
-    for entity in game.entities.iter() {
+impl Default for TerrainCache {
+    fn default() -> TerrainCache {
+        TerrainCache::new()
+    }
+}
+
+fn explored_count(game: &Game) -> usize {
+    game.vision
+        .explored
+        .iter()
+        .filter(|explored| **explored)
+        .count()
+}
+
+impl TerrainCache {
+    // forces a redraw on the next frame, ex: the tileset was reloaded
+    pub fn invalidate(&mut self) {
+        self.transform = None;
+    }
+
+    fn is_stale(&self, game: &Game, transform: &TileTransform) -> bool {
+        self.texture.is_none() || !self.drawn_for(game, transform)
+    }
+
+    // whether what the texture holds was drawn for this game and transform
+    fn drawn_for(&self, game: &Game, transform: &TileTransform) -> bool {
+        match &self.transform {
+            Some(cached) => {
+                self.revision == game.terrain_revision
+                    && self.explored == explored_count(game)
+                    && cached == transform
+            }
+            None => false,
+        }
+    }
+
+    fn set_drawn(&mut self, game: &Game, transform: TileTransform) {
+        self.revision = game.terrain_revision;
+        self.explored = explored_count(game);
+        self.transform = Some(transform);
+    }
+}
+
+// has to run outside of begin_drawing, raylib can't render to a texture mid frame
+pub fn update_terrain_cache(
+    mut rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    cache: &mut TerrainCache,
+    game: &Game,
+    tileset: &Tileset,
+    textures: &Textures,
+) -> Result<(), String> {
+    let transform = TileTransform::from_settings(&game.settings);
+
+    if !cache.is_stale(game, &transform) {
+        return Ok(());
+    }
+
+    let width = transform.map_width().ceil() as u32;
+    let height = transform.map_height().ceil() as u32;
+
+    let size_changed = match &cache.texture {
+        Some(texture) => {
+            texture.texture.width as u32 != width || texture.texture.height as u32 != height
+        }
+        None => true,
+    };
+
+    if size_changed {
+        cache.texture = Some(rl.load_render_texture(thread, width, height)?);
+    }
+
+    if let Some(texture) = cache.texture.as_mut() {
+        let mut d = rl.begin_texture_mode(thread, texture);
+        d.clear_background(Color::BLACK);
+
+        grid(&mut d, &game.settings);
+
+        for item in Render::terrain_items(game, tileset).iter() {
+            sprite(&mut d, &transform, tileset, textures, item);
+        }
+    }
+
+    cache.set_drawn(game, transform);
+
+    Ok(())
+}
+
+pub fn terrain(d: &mut RaylibDrawHandle, cache: &TerrainCache) {
+    if let Some(texture) = cache.texture.as_ref() {
+        // render textures come out upside down, hence the negative height
         let source_rec = Rectangle::new(
-            tile_side_size * tile_map.get(&entity.characteristics.face).unwrap().x,
-            tile_side_size * tile_map.get(&entity.characteristics.face).unwrap().y,
-            16.0,
-            16.0,
-        );
-        let dest_rec = Rectangle::new(
-            entity.pos.x.as_f32() * (tile_side_size * scale),
-            entity.pos.y.as_f32() * (tile_side_size * scale),
-            tile_side_size * scale,
-            tile_side_size * scale,
-        );
-        let origin = Vector2::new(0.0, 0.0);
-        d.draw_texture_tiled(
-            tileset_entities,
-            source_rec,
-            dest_rec,
-            origin,
             0.0,
-            scale,
-            match entity.mode {
-                EntityMode::Mob => Color::RED,
-                EntityMode::NPC => Color::SKYBLUE,
-                _ => Color::WHITE,
-            },
+            0.0,
+            texture.texture.width.as_f32(),
+            -texture.texture.height.as_f32(),
         );
+
+        d.draw_texture_rec(texture, source_rec, Vector2::new(0.0, 0.0), Color::WHITE);
+    }
+}
+
+// Draws a draw list with the tileset sprites, terrain comes from the cache filled by
+// update_terrain_cache so only items and actors are drawn one by one
+pub struct RaylibRenderer<'a, 'b> {
+    pub d: &'a mut RaylibDrawHandle<'b>,
+    pub transform: TileTransform,
+    pub tileset: &'a Tileset,
+    pub textures: &'a Textures,
+    pub terrain_cache: &'a TerrainCache,
+}
+
+impl<'a, 'b> Renderer for RaylibRenderer<'a, 'b> {
+    fn render(&mut self, list: &DrawList) {
+        terrain(self.d, self.terrain_cache);
+
+        for item in list
+            .items
+            .iter()
+            .filter(|item| item.layer != Layer::Terrain)
+        {
+            sprite(self.d, &self.transform, self.tileset, self.textures, item);
+        }
     }
 }
 
-pub fn mouse(
+// the message log lives below the map, there are no tiles to point at there
+pub fn get_mouse_tile(d: &RaylibDrawHandle, settings: &Settings) -> Option<Position> {
+    TileTransform::from_settings(settings).screen_to_tile(d.get_mouse_x(), d.get_mouse_y())
+}
+
+pub fn tile_outline(
     d: &mut RaylibDrawHandle,
-    game: &Game,
     settings: &Settings,
-    tileset_terrain: &Texture2D,
-    tileset_entities: &Texture2D,
+    tile_pos: &Position,
+    color: Color,
 ) {
-    let mouse_x = d.get_mouse_x();
-    let mouse_y = d.get_mouse_y();
+    let rect = TileTransform::from_settings(settings).tile_rect(tile_pos);
 
-    let mouse_pointer_color = Color::YELLOW;
+    d.draw_rectangle_lines_ex(rect, 2, color);
+}
 
-    let mouse_tile_pos = Position {
-        x: mouse_x / settings.graphic.tile_width,
-        y: mouse_y / settings.graphic.tile_height,
+pub fn mouse(d: &mut RaylibDrawHandle, settings: &Settings) {
+    let mouse_tile_pos = match get_mouse_tile(d, settings) {
+        Some(tile_pos) => tile_pos,
+        None => return,
     };
 
+    let mouse_pointer_color = Render::palette_colors(settings.palette).highlight;
+
     let text = format!("x: {}, y: {}", mouse_tile_pos.x, mouse_tile_pos.y);
-    d.draw_text(&text, mouse_x + 20, mouse_y + 20, 20, mouse_pointer_color);
-
-    d.draw_line_ex(
-        Vector2 {
-            x: (mouse_tile_pos.x * settings.graphic.tile_width).as_f32(),
-            y: (mouse_tile_pos.y * settings.graphic.tile_height).as_f32(),
-        },
-        Vector2 {
-            x: ((mouse_tile_pos.x + 1) * settings.graphic.tile_width).as_f32(),
-            y: (mouse_tile_pos.y * settings.graphic.tile_height).as_f32(),
-        },
-        2.0,
+    d.draw_text(
+        &text,
+        d.get_mouse_x() + 20,
+        d.get_mouse_y() + 20,
+        settings.hud.log_font_size,
         mouse_pointer_color,
     );
 
-    d.draw_line_ex(
-        Vector2 {
-            x: (mouse_tile_pos.x * settings.graphic.tile_width).as_f32(),
-            y: ((mouse_tile_pos.y + 1) * settings.graphic.tile_height).as_f32(),
-        },
-        Vector2 {
-            x: ((mouse_tile_pos.x + 1) * settings.graphic.tile_width).as_f32(),
-            y: ((mouse_tile_pos.y + 1) * settings.graphic.tile_height).as_f32(),
-        },
-        2.0,
-        mouse_pointer_color,
+    tile_outline(d, settings, &mouse_tile_pos, mouse_pointer_color);
+}
+
+pub fn look(d: &mut RaylibDrawHandle, game: &Game, settings: &Settings, hud: &Hud) {
+    let cursor = match hud.look {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    let colors = Render::palette_colors(settings.palette);
+
+    tile_outline(d, settings, &cursor, colors.look);
+
+    let font_size = settings.hud.log_font_size;
+    let line_height = font_size + 4;
+
+    let mut lines = StateHelpers::describe_tile(game, &cursor);
+    lines.push(String::new());
+    lines.push("[X] stop looking".to_string());
+
+    let panel_width = lines
+        .iter()
+        .map(|line| measure_text(line, font_size))
+        .max()
+        .unwrap_or(0)
+        + 20;
+    let panel_height = lines.len() as i32 * line_height + 10;
+
+    // keep the panel on the other half of the map so it never hides the tile being examined
+    let panel_x = match cursor.x < settings.graphic.columns / 2 {
+        true => settings.window.width - panel_width - 10,
+        false => 10,
+    };
+    let panel_y = 10;
+
+    d.draw_rectangle(
+        panel_x,
+        panel_y,
+        panel_width,
+        panel_height,
+        Color::new(0, 0, 0, 220),
     );
+    d.draw_rectangle_lines(panel_x, panel_y, panel_width, panel_height, colors.look);
 
-    d.draw_line_ex(
-        Vector2 {
-            x: (mouse_tile_pos.x * settings.graphic.tile_width).as_f32(),
-            y: (mouse_tile_pos.y * settings.graphic.tile_height).as_f32(),
-        },
-        Vector2 {
-            x: (mouse_tile_pos.x * settings.graphic.tile_width).as_f32(),
-            y: ((mouse_tile_pos.y + 1) * settings.graphic.tile_height).as_f32(),
-        },
-        2.0,
-        mouse_pointer_color,
+    for (i, line) in lines.iter().enumerate() {
+        d.draw_text(
+            line,
+            panel_x + 10,
+            panel_y + 5 + i as i32 * line_height,
+            font_size,
+            colors.text,
+        );
+    }
+}
+
+// what the creature being talked to says and what can be answered, along the bottom of the
+// map so the both of them stay in sight
+pub fn conversation(d: &mut RaylibDrawHandle, game: &Game, settings: &Settings) {
+    let lines = Talking::describe(game);
+
+    if lines.is_empty() {
+        return;
+    }
+
+    let colors = Render::palette_colors(settings.palette);
+    let font_size = settings.hud.log_font_size;
+    let line_height = font_size + 4;
+
+    let panel_width = lines
+        .iter()
+        .map(|line| measure_text(line, font_size))
+        .max()
+        .unwrap_or(0)
+        + 20;
+    let panel_height = lines.len() as i32 * line_height + 10;
+    let panel_x = (settings.window.width - panel_width) / 2;
+    let panel_y = settings.window.height - panel_height - 10;
+
+    d.draw_rectangle(
+        panel_x,
+        panel_y,
+        panel_width,
+        panel_height,
+        Color::new(0, 0, 0, 220),
+    );
+    d.draw_rectangle_lines(
+        panel_x,
+        panel_y,
+        panel_width,
+        panel_height,
+        colors.highlight,
     );
 
-    d.draw_line_ex(
-        Vector2 {
-            x: ((mouse_tile_pos.x + 1) * settings.graphic.tile_width).as_f32(),
-            y: (mouse_tile_pos.y * settings.graphic.tile_height).as_f32(),
-        },
-        Vector2 {
-            x: ((mouse_tile_pos.x + 1) * settings.graphic.tile_width).as_f32(),
-            y: ((mouse_tile_pos.y + 1) * settings.graphic.tile_height).as_f32(),
-        },
-        2.0,
-        mouse_pointer_color,
+    for (i, line) in lines.iter().enumerate() {
+        // the name of whoever is talking stands out from what they say
+        let color = match i {
+            0 => colors.highlight,
+            _ => colors.text,
+        };
+
+        d.draw_text(
+            line,
+            panel_x + 10,
+            panel_y + 5 + i as i32 * line_height,
+            font_size,
+            color,
+        );
+    }
+}
+
+// pixels per tile on the minimap
+const MINIMAP_BLOCK: f32 = 4.0;
+const MINIMAP_MARGIN: f32 = 10.0;
+
+// bottom right corner of the map, away from the look panel
+fn minimap_rect(settings: &Settings) -> Rectangle {
+    let transform = TileTransform::from_settings(settings);
+    let width = transform.columns.as_f32() * MINIMAP_BLOCK;
+    let height = transform.rows.as_f32() * MINIMAP_BLOCK;
+
+    Rectangle::new(
+        transform.map_width() - width - MINIMAP_MARGIN,
+        transform.map_height() - height - MINIMAP_MARGIN,
+        width,
+        height,
+    )
+}
+
+// the map tile under the mouse when it is over the minimap
+pub fn get_minimap_tile(d: &RaylibDrawHandle, settings: &Settings, hud: &Hud) -> Option<Position> {
+    if !hud.minimap {
+        return None;
+    }
+
+    let rect = minimap_rect(settings);
+    let x = d.get_mouse_x().as_f32() - rect.x;
+    let y = d.get_mouse_y().as_f32() - rect.y;
+
+    match x >= 0.0 && y >= 0.0 && x < rect.width && y < rect.height {
+        true => Some(Position {
+            x: (x / MINIMAP_BLOCK) as i32,
+            y: (y / MINIMAP_BLOCK) as i32,
+        }),
+        false => None,
+    }
+}
+
+pub fn minimap(d: &mut RaylibDrawHandle, game: &Game, settings: &Settings, hud: &Hud) {
+    if !hud.minimap {
+        return;
+    }
+
+    let rect = minimap_rect(settings);
+    let colors = Render::palette_colors(settings.palette);
+    let block = |pos: &Position| {
+        Rectangle::new(
+            rect.x + pos.x.as_f32() * MINIMAP_BLOCK,
+            rect.y + pos.y.as_f32() * MINIMAP_BLOCK,
+            MINIMAP_BLOCK,
+            MINIMAP_BLOCK,
+        )
+    };
+
+    d.draw_rectangle_rec(
+        Rectangle::new(
+            rect.x - 2.0,
+            rect.y - 2.0,
+            rect.width + 4.0,
+            rect.height + 4.0,
+        ),
+        Color::new(0, 0, 0, 220),
+    );
+
+    // only what was seen shows up, and what isn't in sight anymore is dimmed
+    for building in game.buildings.iter() {
+        if !game.vision.is_explored(&building.pos) {
+            continue;
+        }
+
+        let color = match building.characteristics.walkable {
+            true => colors.minimap_floor,
+            false => colors.minimap_wall,
+        };
+
+        d.draw_rectangle_rec(
+            block(&building.pos),
+            match game.vision.is_visible(&building.pos) {
+                true => color,
+                false => color.fade(0.4),
+            },
+        );
+    }
+
+    for entity in game.entities.iter() {
+        match entity.mode {
+            EntityMode::Player => d.draw_rectangle_rec(block(&entity.pos), Color::WHITE),
+            _ if game.vision.is_visible(&entity.pos) => {
+                d.draw_rectangle_rec(block(&entity.pos), entity.characteristics.color)
+            }
+            _ => (),
+        }
+    }
+
+    if let Some(cursor) = hud.look {
+        d.draw_rectangle_lines_ex(block(&cursor), 1, colors.look);
+    }
+
+    d.draw_rectangle_lines_ex(
+        Rectangle::new(
+            rect.x - 2.0,
+            rect.y - 2.0,
+            rect.width + 4.0,
+            rect.height + 4.0,
+        ),
+        1,
+        colors.border,
+    );
+}
+
+pub fn message_log(d: &mut RaylibDrawHandle, game: &Game, settings: &Settings, hud: &Hud) {
+    let panel_y = settings.window.height;
+    let line_height = settings.hud.log_font_size + 2;
+    let colors = Render::palette_colors(settings.palette);
+
+    d.draw_rectangle(
+        0,
+        panel_y,
+        settings.window.width,
+        settings.hud.log_height,
+        colors.panel,
+    );
+    d.draw_line(0, panel_y, settings.window.width, panel_y, colors.border);
+
+    let last = game.messages.len().saturating_sub(hud.log_scroll);
+    let first = last.saturating_sub(settings.hud.log_lines as usize);
+
+    for (i, message) in game.messages[first..last].iter().enumerate() {
+        let text = format!("[{}] {}", message.turn, message.text);
+
+        d.draw_text(
+            &text,
+            10,
+            panel_y + 5 + i as i32 * line_height,
+            settings.hud.log_font_size,
+            match message.kind {
+                MessageKind::Info => colors.text,
+                MessageKind::Warning => colors.warning,
+            },
+        );
+    }
+
+    if hud.log_scroll > 0 {
+        let text = format!("-- {} newer --", hud.log_scroll);
+        let text_width = measure_text(&text, settings.hud.log_font_size);
+
+        d.draw_text(
+            &text,
+            settings.window.width - text_width - 10,
+            panel_y + 5,
+            settings.hud.log_font_size,
+            Color::GRAY,
+        );
+    }
+}
+
+// every screen but the game itself, drawn over whatever is already there
+pub fn menu(
+    d: &mut RaylibDrawHandle,
+    settings: &Settings,
+    title: &str,
+    items: &[MenuItem],
+    selected: usize,
+    notice: Option<&str>,
+) {
+    let width = settings.window.width;
+    let height = settings.window.height + settings.hud.log_height;
+
+    let colors = Render::palette_colors(settings.palette);
+    let font_size = settings.hud.log_font_size;
+
+    d.draw_rectangle(0, 0, width, height, Color::new(0, 0, 0, 200));
+
+    let title_size = 40;
+    let item_size = font_size + 4;
+    let line_height = item_size + 10;
+    let mut y = height / 3;
+
+    d.draw_text(
+        title,
+        (width - measure_text(title, title_size)) / 2,
+        y,
+        title_size,
+        colors.text,
     );
+    y += title_size + 30;
 
-    let entities_under_mouse = StateHelpers::get_entities_in_tile(&game, &mouse_tile_pos);
+    for (i, item) in items.iter().enumerate() {
+        let (text, color) = match (i == selected, item.enabled) {
+            (_, false) => (item.label.to_owned(), Color::DARKGRAY),
+            (true, true) => (format!("> {} <", item.label), colors.highlight),
+            (false, true) => (item.label.to_owned(), colors.text),
+        };
 
-    let mut i = 0;
-    for entity in entities_under_mouse {
-        let print_entity = format!(
-            "[Entity]: Mode -> '{:#?}' Relationship -> '{:#?}'",
-            entity.mode, entity.relationship
+        d.draw_text(
+            &text,
+            (width - measure_text(&text, item_size)) / 2,
+            y,
+            item_size,
+            color,
         );
+        y += line_height;
+    }
+
+    if let Some(notice) = notice {
+        d.draw_text(
+            notice,
+            (width - measure_text(notice, font_size)) / 2,
+            y + 10,
+            font_size,
+            colors.warning,
+        );
+    }
+
+    let keys = StateCore::get_movement_key_names(settings.keys);
+    let hint = format!(
+        "{}/{} to move, Enter to pick, Escape to go back",
+        keys[1], keys[2]
+    );
+    d.draw_text(
+        &hint,
+        (width - measure_text(&hint, font_size)) / 2,
+        height - font_size * 2,
+        font_size,
+        Color::GRAY,
+    );
+}
+
+pub fn effects(
+    d: &mut RaylibDrawHandle,
+    list: &DrawList,
+    transform: &TileTransform,
+    tileset: &Tileset,
+    textures: &Textures,
+    colors: &PaletteColors,
+    effects: &Effects,
+) {
+    for effect in effects.list.iter() {
+        let fading = 1.0 - effect.progress();
+
+        match &effect.kind {
+            EffectKind::FloatingText { text, color } => {
+                let font_size = 20;
+                let corner = transform.tile_to_screen(&effect.pos);
+                let rise = effect.progress() * transform.tile_height;
 
-        d.draw_text(&print_entity, 0, i * 22, 20, Color::WHITE);
+                d.draw_text(
+                    text,
+                    (corner.x
+                        + (transform.tile_width - measure_text(text, font_size).as_f32()) / 2.0)
+                        as i32,
+                    (corner.y - rise) as i32,
+                    font_size,
+                    color.fade(fading),
+                );
+            }
+            // the sprite again on top of itself, tinted, following it if it moves
+            EffectKind::Flash { id } => {
+                if let Some(item) = list.items.iter().find(|item| item.id == *id) {
+                    let mut flash = item.clone();
+                    flash.color = colors.hurt.fade(fading * 0.8);
 
-        i += 1;
+                    sprite(d, transform, tileset, textures, &flash);
+                }
+            }
+            EffectKind::Fade { entity } => {
+                let mut item = Render::actor_item(entity, tileset);
+                item.color = item.color.fade(fading);
+
+                sprite(d, transform, tileset, textures, &item);
+            }
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn frame(
     d: &mut RaylibDrawHandle,
     game: &Game,
-    settings: &Settings,
-    tileset_terrain: &Texture2D,
-    tileset_entities: &Texture2D,
+    hud: &Hud,
+    tileset: &Tileset,
+    textures: &Textures,
+    terrain_cache: &TerrainCache,
+    animator: &Animator,
+    visual_effects: &Effects,
 ) {
-    grid(d, &settings);
-    entities(d, game, &settings, tileset_terrain, tileset_entities);
-    mouse(d, game, &settings, tileset_terrain, tileset_entities);
+    let settings = &game.settings;
+    let transform = TileTransform::from_settings(settings);
+
+    let mut list = Render::draw_list(game, tileset);
+    animator.apply(&mut list);
+
+    let mut renderer = RaylibRenderer {
+        d,
+        transform,
+        tileset,
+        textures,
+        terrain_cache,
+    };
+    renderer.render(&list);
+
+    let colors = Render::palette_colors(settings.palette);
+    effects(
+        d,
+        &list,
+        &transform,
+        tileset,
+        textures,
+        &colors,
+        visual_effects,
+    );
+    message_log(d, game, settings, hud);
+    minimap(d, game, settings, hud);
+    mouse(d, settings);
+    look(d, game, settings, hud);
+    conversation(d, game, settings);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::domain::{EntityAi, EntityRelationship};
+    use crate::state::test_support as TestSupport;
+
+    #[test]
+    fn tiles_and_screen_points_map_back_and_forth() {
+        // 130 pixels don't split evenly in 4 columns, the last 2 are left outside the map
+        let settings = StateHelpers::get_settings(90, 130, 3, 4, 20);
+        let transform = TileTransform::from_settings(&settings);
+
+        assert_eq!(transform.map_width(), 128.0);
+        assert_eq!(transform.map_height(), 90.0);
+        assert_eq!(
+            transform.tile_rect(&Position { x: 2, y: 1 }),
+            Rectangle::new(64.0, 30.0, 32.0, 30.0)
+        );
+
+        for y in 0..3 {
+            for x in 0..4 {
+                let corner = transform.tile_to_screen(&Position { x, y });
+                let (left, top) = (corner.x as i32, corner.y as i32);
+
+                assert_eq!(transform.screen_to_tile(left, top), Some(Position { x, y }));
+                assert_eq!(
+                    transform.screen_to_tile(left + 31, top + 29),
+                    Some(Position { x, y })
+                );
+            }
+        }
+
+        assert_eq!(transform.screen_to_tile(128, 10), None);
+        assert_eq!(transform.screen_to_tile(10, 90), None);
+        assert_eq!(transform.screen_to_tile(-1, 10), None);
+    }
+
+    #[test]
+    fn the_terrain_cache_redraws_only_when_the_map_or_its_layout_changed() {
+        let settings = StateHelpers::get_settings(90, 130, 3, 4, 20);
+        let buildings = TestSupport::tiles(&settings, |x, y| match (x, y) {
+            (1..=2, 1) => EntityMode::Ground,
+            _ => EntityMode::BrickWall,
+        });
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let mut game = TestSupport::game(
+            settings,
+            buildings,
+            vec![StateHelpers::gen_creature(&player, Position { x: 1, y: 1 })],
+        );
+        let transform = TileTransform::from_settings(&settings);
+
+        let mut cache = TerrainCache::new();
+        assert!(cache.is_stale(&game, &transform));
+
+        cache.set_drawn(&game, transform);
+        assert!(cache.drawn_for(&game, &transform));
+
+        let resized = TileTransform::from_settings(&StateHelpers::get_settings(180, 260, 3, 4, 20));
+        assert!(!cache.drawn_for(&game, &resized));
+
+        game.terrain_revision += 1;
+        assert!(!cache.drawn_for(&game, &transform));
+
+        // newly explored tiles show up
+        let tiles = game.vision.explored.len();
+        game.vision.explored = vec![false; tiles];
+        cache.set_drawn(&game, transform);
+        game.vision.explored = vec![true; tiles];
+        assert!(!cache.drawn_for(&game, &transform));
+        cache.set_drawn(&game, transform);
+
+        cache.invalidate();
+        assert!(!cache.drawn_for(&game, &transform));
+    }
 }
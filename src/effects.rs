@@ -0,0 +1,172 @@
+use crate::color::Color;
+use crate::render as Render;
+use crate::state::domain::{Entity, Game, GameEvent, Position};
+use crate::state::helpers as StateHelpers;
+use uuid::Uuid;
+
+// Short lived eye candy spawned from what happened during a turn. Each effect lives for a
+// fixed number of frames, whatever the turns are doing meanwhile.
+
+const FLOATING_TEXT_FRAMES: u32 = 45;
+const FLASH_FRAMES: u32 = 12;
+const FADE_FRAMES: u32 = 30;
+
+#[derive(Debug, Clone)]
+pub enum EffectKind {
+    FloatingText { text: String, color: Color },
+    Flash { id: Uuid },
+    Fade { entity: Entity },
+}
+
+#[derive(Debug, Clone)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub pos: Position,
+    pub frames: u32,
+    pub age: u32,
+}
+
+impl Effect {
+    fn new(kind: EffectKind, pos: Position, frames: u32) -> Effect {
+        Effect {
+            kind,
+            pos,
+            frames,
+            age: 0,
+        }
+    }
+
+    // 0.0 when spawned, 1.0 on its last frame
+    pub fn progress(&self) -> f32 {
+        self.age as f32 / self.frames.max(1) as f32
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Effects {
+    pub list: Vec<Effect>,
+}
+
+impl Effects {
+    pub fn new() -> Effects {
+        Effects { list: Vec::new() }
+    }
+
+    pub fn spawn(&mut self, game: &Game) {
+        let player_id = StateHelpers::get_player(game).map(|player| player.id);
+        let colors = Render::palette_colors(game.settings.palette);
+
+        for event in game.events.iter() {
+            match event {
+                GameEvent::Attacked {
+                    target,
+                    pos,
+                    damage,
+                    ..
+                }
+                | GameEvent::Hurt {
+                    id: target,
+                    pos,
+                    damage,
+                } => {
+                    // the player getting hurt stands out from the player hurting things
+                    let color = match Some(*target) == player_id {
+                        true => colors.hurt,
+                        false => colors.hit,
+                    };
+
+                    let text = match damage {
+                        0 => "miss".to_string(),
+                        damage => damage.to_string(),
+                    };
+
+                    self.list.push(Effect::new(
+                        EffectKind::FloatingText { text, color },
+                        *pos,
+                        FLOATING_TEXT_FRAMES,
+                    ));
+
+                    if *damage > 0 {
+                        self.list.push(Effect::new(
+                            EffectKind::Flash { id: *target },
+                            *pos,
+                            FLASH_FRAMES,
+                        ));
+                    }
+                }
+                GameEvent::Died { entity } => self.list.push(Effect::new(
+                    EffectKind::Fade {
+                        entity: entity.to_owned(),
+                    },
+                    entity.pos,
+                    FADE_FRAMES,
+                )),
+                _ => (),
+            }
+        }
+    }
+
+    // called once per frame
+    pub fn tick(&mut self) {
+        for effect in self.list.iter_mut() {
+            effect.age += 1;
+        }
+
+        self.list.retain(|effect| effect.age < effect.frames);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::domain::{EntityAi, EntityRelationship};
+    use crate::state::test_support as TestSupport;
+
+    #[test]
+    fn the_map_hurting_the_player_shows_like_a_hit() {
+        let settings = StateHelpers::get_settings(60, 60, 3, 3, 20);
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let player = StateHelpers::gen_creature(&player, Position { x: 1, y: 1 });
+        let mut game = TestSupport::game(settings, Vec::new(), vec![player.to_owned()]);
+        let colors = Render::palette_colors(settings.palette);
+
+        game.events = vec![
+            GameEvent::Hurt {
+                id: player.id,
+                pos: player.pos,
+                damage: 3,
+            },
+            GameEvent::Attacked {
+                attacker: player.id,
+                target: Uuid::new_v4(),
+                pos: Position { x: 2, y: 1 },
+                damage: 2,
+            },
+        ];
+
+        let mut effects = Effects::new();
+        effects.spawn(&game);
+
+        let texts: Vec<(String, Color, Position)> = effects
+            .list
+            .iter()
+            .filter_map(|effect| match &effect.kind {
+                EffectKind::FloatingText { text, color } => {
+                    Some((text.to_owned(), *color, effect.pos))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            vec![
+                ("3".to_string(), colors.hurt, player.pos),
+                ("2".to_string(), colors.hit, Position { x: 2, y: 1 }),
+            ]
+        );
+        assert!(effects
+            .list
+            .iter()
+            .any(|effect| matches!(effect.kind, EffectKind::Flash { id } if id == player.id)));
+    }
+}
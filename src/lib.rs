@@ -0,0 +1,26 @@
+pub mod app;
+
+pub mod color;
+
+pub mod content;
+
+pub mod diagnostics;
+
+pub mod state;
+
+#[cfg(feature = "raylib")]
+pub mod draw;
+
+pub mod render;
+
+pub mod animation;
+
+pub mod effects;
+
+pub mod save;
+
+pub mod user_settings;
+
+pub mod replay;
+
+pub mod cli;
@@ -1,92 +1,888 @@
-use crate::draw as DrawGame;
-use crate::state::core as StateCore;
-use crate::state::domain::{Entity, Game, Settings};
-use crate::state::helpers as StateHelpers;
-use raylib::color::Color;
-use raylib::consts::KeyboardKey;
-use raylib::prelude::RaylibDraw;
-use std::collections::HashMap;
+use poorguelike::animation::Animator;
+use poorguelike::app::{App, MenuAction, Screen};
+use poorguelike::cli as Cli;
+use poorguelike::cli::Options;
+use poorguelike::color::Color;
+use poorguelike::content::creatures as Creatures;
+use poorguelike::content::creatures::{CreatureTemplate, CREATURES_FILE};
+use poorguelike::content::dialogues as Dialogues;
+use poorguelike::content::dialogues::DIALOGUES_FILE;
+use poorguelike::content::tileset as TilesetContent;
+use poorguelike::content::tileset::{Tileset, TILESET_FILE};
+use poorguelike::content::watch::ContentWatcher;
+use poorguelike::content::ContentError;
+use poorguelike::diagnostics as Diagnostics;
+use poorguelike::diagnostics::{Level, TARGET_GAME, TARGET_INPUT};
+use poorguelike::draw as DrawGame;
+use poorguelike::draw::{TerrainCache, Textures};
+use poorguelike::effects::Effects;
+use poorguelike::render as Render;
+use poorguelike::render::ascii as AsciiRender;
+use poorguelike::replay as ReplayFile;
+use poorguelike::replay::{Replay, REPLAY_FILE};
+use poorguelike::save as Save;
+use poorguelike::save::SAVE_FILE;
+use poorguelike::state::core as StateCore;
+use poorguelike::state::domain::{Command, Game, Hud, Key, MessageKind, Settings};
+use poorguelike::state::helpers as StateHelpers;
+use poorguelike::state::random as Random;
+use poorguelike::state::travel as Travelling;
+use poorguelike::state::travel::Travel;
+use poorguelike::user_settings as UserSettingsFile;
+use poorguelike::user_settings::UserSettings;
+use raylib::consts::{KeyboardKey, MouseButton};
+use raylib::core::input::key_from_i32;
+use raylib::drawing::RaylibDrawHandle;
+use raylib::prelude::{RaylibDraw, RaylibHandle, RaylibThread};
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::path::Path;
 
-mod state;
+// gameplay keys pressed while a turn is still animating wait here, a few at most so the
+// game never plays catch up for long
+const MAX_PENDING_COMMANDS: usize = 2;
 
-mod draw;
+fn debug(game: &Game, tileset: &Tileset) {
+    if !Diagnostics::enabled(Level::Debug, TARGET_GAME) {
+        return;
+    }
 
-fn debug(game: &Game) {
-    println!("Entities:");
+    Diagnostics::log(
+        Level::Debug,
+        TARGET_GAME,
+        "map",
+        &[
+            ("turn", &game.turn),
+            (
+                "tiles",
+                &AsciiRender::render_to_string(&Render::draw_list(game, tileset)),
+            ),
+        ],
+    );
 
     for entity in game.entities.iter() {
-        print!("\t- Entity {}\n", entity.id);
-        print!("\t\t- Mode {:#?}\n", entity.mode);
-        print!("\t\t- Relationship {:#?}\n", entity.relationship);
-        print!("\t\t- Characteristics\n");
-        print!("\t\t\t- Face {:#?}\n", entity.characteristics.face);
-        print!(
-            "\t\t\t- Color: r={} g={} b={}\n",
-            entity.characteristics.color.r,
-            entity.characteristics.color.g,
-            entity.characteristics.color.b
+        Diagnostics::log(
+            Level::Debug,
+            TARGET_GAME,
+            "entity",
+            &[
+                ("turn", &game.turn),
+                ("id", &entity.id),
+                ("template", &entity.template),
+                ("mode", &format!("{:?}", entity.mode)),
+                ("relationship", &format!("{:?}", entity.relationship)),
+                ("face", &format!("{:?}", entity.characteristics.face)),
+                (
+                    "color",
+                    &format!(
+                        "{},{},{}",
+                        entity.characteristics.color.r,
+                        entity.characteristics.color.g,
+                        entity.characteristics.color.b
+                    ),
+                ),
+                ("x", &entity.pos.x),
+                ("y", &entity.pos.y),
+            ],
+        );
+    }
+}
+
+fn report_content_error(game: &mut Game, error: &ContentError) {
+    Diagnostics::log(
+        Level::Warn,
+        TARGET_GAME,
+        "could not reload content",
+        &[("error", error)],
+    );
+
+    game.messages.push(StateHelpers::gen_message(
+        &format!("Reload failed: {}", error),
+        MessageKind::Warning,
+        game.turn,
+    ));
+}
+
+// reapplies content files edited while playing, anything wrong with them ends up in the
+// message log and the game keeps going with what it had
+fn reload_content(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    changed: &[String],
+    game: &mut Game,
+    tileset: &mut Tileset,
+    textures: &mut Textures,
+    creatures: &mut Vec<CreatureTemplate>,
+) {
+    let tileset_changed = changed.iter().any(|path| path == TILESET_FILE);
+
+    let new_tileset = match tileset_changed {
+        true => match TilesetContent::load_tileset(TILESET_FILE) {
+            Ok(new_tileset) => new_tileset,
+            Err(error) => return report_content_error(game, &error),
+        },
+        false => tileset.to_owned(),
+    };
+
+    let new_dialogues = match Dialogues::load_dialogues(DIALOGUES_FILE) {
+        Ok(new_dialogues) => new_dialogues,
+        Err(error) => return report_content_error(game, &error),
+    };
+
+    // creatures are checked against the new tileset too, a sprite may have been renamed
+    let new_creatures =
+        match Creatures::load_creatures(CREATURES_FILE, &new_tileset, &new_dialogues) {
+            Ok(new_creatures) => new_creatures,
+            Err(error) => return report_content_error(game, &error),
+        };
+
+    if tileset_changed {
+        match DrawGame::load_textures(rl, thread, &new_tileset) {
+            Ok(new_textures) => *textures = new_textures,
+            Err(error) => {
+                game.messages.push(StateHelpers::gen_message(
+                    &format!("Reload failed: {}", error),
+                    MessageKind::Warning,
+                    game.turn,
+                ));
+                return;
+            }
+        }
+
+        *tileset = new_tileset;
+    }
+
+    let updated = StateCore::apply_creature_templates(game, &new_creatures);
+    *creatures = new_creatures;
+
+    Diagnostics::log(
+        Level::Info,
+        TARGET_GAME,
+        "content reloaded",
+        &[("files", &changed.join(",")), ("updated", &updated)],
+    );
+
+    game.messages.push(StateHelpers::gen_message(
+        &format!(
+            "Reloaded {} ({} creatures updated).",
+            changed.join(", "),
+            updated
+        ),
+        MessageKind::Info,
+        game.turn,
+    ));
+}
+
+// the settings file, with the command line on top of it for this run
+struct Config {
+    user: UserSettings,
+    options: Options,
+    // tile scales are counted in atlas pixels
+    tile_size: i32,
+}
+
+impl Config {
+    // settings for a game on a map of the given size
+    fn settings(&self, rows: i32, columns: i32) -> Settings {
+        self.options
+            .apply(&self.user.to_settings(rows, columns, self.tile_size))
+    }
+
+    fn new_game_settings(&self) -> Settings {
+        let (columns, rows) = self
+            .options
+            .map_size
+            .unwrap_or((self.user.columns, self.user.rows));
+
+        self.settings(rows, columns)
+    }
+
+    fn save_file(&self) -> &str {
+        self.options.load.as_deref().unwrap_or(SAVE_FILE)
+    }
+}
+
+// everything about the game being played, rebuilt on a new game or a load
+struct Play {
+    game: Game,
+    hud: Hud,
+    animator: Animator,
+    effects: Effects,
+    pending_commands: VecDeque<Command>,
+    // how the game started and what was played since, loaded games can't be replayed
+    replay: Option<Replay>,
+    // commands of a replay being played back, fed one by one as if they were typed
+    replaying: VecDeque<Command>,
+    // playing back a replay, the player's own save is left alone
+    watching: bool,
+    // a walk to a clicked tile, one step per turn
+    travel: Option<Travel>,
+}
+
+impl Play {
+    fn new(game: Game) -> Play {
+        Play {
+            game,
+            hud: Hud {
+                log_scroll: 0,
+                look: None,
+                last_mouse_tile: None,
+                minimap: false,
+                count: 0,
+            },
+            animator: Animator::new(),
+            effects: Effects::new(),
+            pending_commands: VecDeque::new(),
+            replay: None,
+            replaying: VecDeque::new(),
+            watching: false,
+            travel: None,
+        }
+    }
+
+    // switches to another game, keeping debug logging as it was
+    fn replace(&mut self, game: Game, config: &Config) {
+        let debug = self.game.settings.debug;
+
+        *self = Play::new(game);
+        self.game.settings.debug = debug;
+        self.apply(config);
+    }
+
+    // what the player chose in the options, the map keeps its size until the next game
+    fn apply(&mut self, config: &Config) {
+        let graphic = self.game.settings.graphic;
+
+        self.game.settings = Settings {
+            debug: self.game.settings.debug,
+            world: self.game.settings.world,
+            ..config.settings(graphic.rows, graphic.columns)
+        };
+        self.hud.minimap = config.user.minimap;
+    }
+
+    // a new game, recorded from the start
+    fn start(&mut self, seed: u64, config: &Config, creatures: &[CreatureTemplate]) {
+        let settings = config.new_game_settings();
+        let replay = Replay::new(seed, &settings);
+
+        self.replace(replay.start(&settings, creatures), config);
+        self.replay = Some(replay);
+    }
+
+    // the recorded game from its start, its commands get played again one by one and
+    // recorded again along the way
+    fn watch(&mut self, replay: Replay, config: &Config, creatures: &[CreatureTemplate]) {
+        self.replace(replay.start(&config.new_game_settings(), creatures), config);
+        self.replaying = replay.commands.iter().copied().collect();
+        self.watching = true;
+        self.replay = Some(Replay {
+            commands: Vec::new(),
+            ..replay
+        });
+    }
+}
+
+// every atlas is expected to use the same tile size
+fn tile_size(tileset: &Tileset) -> i32 {
+    tileset
+        .atlases
+        .first()
+        .map(|atlas| atlas.tile_size)
+        .unwrap_or(16)
+}
+
+fn store_user_settings(user: &UserSettings) -> Result<(), String> {
+    let path = UserSettingsFile::settings_path();
+
+    UserSettingsFile::save_user_settings(&path, user).inspect_err(|error| {
+        Diagnostics::log(
+            Level::Warn,
+            TARGET_GAME,
+            "could not save settings",
+            &[("error", error)],
+        );
+    })
+}
+
+fn update_playing(
+    d: &RaylibDrawHandle,
+    command: Option<Command>,
+    play: &mut Play,
+    app: &mut App,
+    config: &Config,
+    tileset: &Tileset,
+) {
+    // any key stops a walk, and does nothing else
+    if command.is_some() && play.travel.take().is_some() {
+        return;
+    }
+
+    if command == Some(Command::Cancel)
+        && play.hud.look.is_none()
+        && play.game.conversation.is_none()
+    {
+        app.go_to(Screen::Paused);
+        return;
+    }
+
+    // the minimap sits on top of the map, the mouse over it isn't pointing at map tiles
+    let minimap_tile = DrawGame::get_minimap_tile(d, &play.game.settings, &play.hud);
+
+    StateCore::update_look_with_mouse(
+        &mut play.hud,
+        match minimap_tile {
+            Some(_) => None,
+            None => DrawGame::get_mouse_tile(d, &play.game.settings),
+        },
+        d.is_mouse_button_pressed(MouseButton::MOUSE_RIGHT_BUTTON),
+    );
+
+    if d.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
+        match minimap_tile {
+            Some(tile) => play.hud.look = Some(tile),
+            None if play.hud.look.is_none() && play.replaying.is_empty() => {
+                play.travel = DrawGame::get_mouse_tile(d, &play.game.settings)
+                    .and_then(|tile| Travelling::start_travel(&play.game, tile));
+            }
+            None => (),
+        }
+    }
+
+    if let Some(command) = command {
+        Diagnostics::log(
+            Level::Debug,
+            TARGET_INPUT,
+            "command",
+            &[
+                ("command", &format!("{:?}", command)),
+                ("turn", &play.game.turn),
+            ],
+        );
+
+        // a count only lasts until the next command
+        let count = match command {
+            Command::Count(_) => 0,
+            _ => std::mem::take(&mut play.hud.count),
+        };
+        let run = d.is_key_down(KeyboardKey::KEY_LEFT_SHIFT)
+            || d.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+
+        // the replay has the keyboard until it's over
+        let travel = match play.hud.look.is_none() && play.replaying.is_empty() {
+            true => Travelling::start_for_command(&play.game, command, count, run),
+            false => None,
+        };
+
+        if travel.is_some() {
+            play.travel = travel;
+        } else if StateCore::is_gameplay_command(&command, &play.hud, &play.game) {
+            if play.replaying.is_empty() && play.pending_commands.len() < MAX_PENDING_COMMANDS {
+                play.pending_commands.push_back(command);
+            }
+        } else {
+            StateCore::update_hud(&mut play.hud, &mut play.game, command);
+        }
+    }
+
+    if play.pending_commands.is_empty() {
+        if let Some(command) = play.replaying.pop_front() {
+            play.pending_commands.push_back(command);
+        }
+    }
+
+    if play.pending_commands.is_empty() && !play.animator.is_busy() {
+        if let Some(travel) = play.travel.as_mut() {
+            match Travelling::take_travel_step(&mut play.game, travel) {
+                Some(command) => play.pending_commands.push_back(command),
+                None => play.travel = None,
+            }
+        }
+    }
+
+    if !play.game.settings.animations {
+        play.animator.skip();
+    }
+
+    play.animator.update(d.get_frame_time());
+
+    if !play.animator.is_busy() {
+        if let Some(command) = play.pending_commands.pop_front() {
+            play.game = StateCore::update_game_states(&play.game, &play.game.settings, command);
+            play.hud.log_scroll = 0;
+
+            if let Some(replay) = play.replay.as_mut() {
+                replay.commands.push(command);
+            }
+
+            if play.game.settings.animations {
+                play.animator.start(&play.game.events);
+            }
+            play.effects.spawn(&play.game);
+
+            debug(&play.game, tileset);
+        }
+    }
+
+    // dead is dead, the save goes away once the death has played out on screen (a death in
+    // a replay being watched isn't the player's)
+    if StateHelpers::get_player(&play.game).is_none()
+        && !play.animator.is_busy()
+        && play.effects.list.is_empty()
+    {
+        if !play.watching {
+            let _ = fs::remove_file(config.save_file());
+        }
+        keep_replay(play);
+
+        Diagnostics::log(
+            Level::Info,
+            TARGET_GAME,
+            "game over",
+            &[("turn", &play.game.turn)],
+        );
+
+        app.go_to(Screen::GameOver);
+    }
+}
+
+// the last game played from its start is kept to be watched again with --replay, unless it
+// is a replay that wasn't watched to the end
+fn keep_replay(play: &Play) {
+    let replay = match (&play.replay, play.replaying.is_empty()) {
+        (Some(replay), true) => replay,
+        _ => return,
+    };
+
+    if let Err(error) = ReplayFile::save_replay(REPLAY_FILE, replay) {
+        Diagnostics::log(
+            Level::Warn,
+            TARGET_GAME,
+            "could not keep the replay",
+            &[("error", &error)],
         );
-        print!("\t\t- Position: x={} y={}\n", entity.pos.x, entity.pos.y);
-        println!();
+    }
+}
+
+fn save(play: &Play, config: &Config) -> Result<(), String> {
+    // a replay is somebody's old game, saving it would take the place of the player's own
+    if play.watching {
+        keep_replay(play);
+        return Ok(());
+    }
+
+    let path = config.save_file();
+
+    Save::save_game(path, &play.game)?;
+    keep_replay(play);
+
+    Diagnostics::log(
+        Level::Info,
+        TARGET_GAME,
+        "game saved",
+        &[("file", &path), ("turn", &play.game.turn)],
+    );
+
+    Ok(())
+}
+
+fn run_menu_action(
+    action: MenuAction,
+    app: &mut App,
+    play: &mut Play,
+    config: &mut Config,
+    creatures: &[CreatureTemplate],
+) {
+    app.notice = None;
+
+    match action {
+        MenuAction::NewGame => {
+            // --seed only picks the first game, the ones after it are new ones
+            let seed = config
+                .options
+                .seed
+                .take()
+                .unwrap_or_else(Random::random_seed);
+
+            play.start(seed, config, creatures);
+            app.go_to(Screen::Playing);
+        }
+        MenuAction::Continue => match Save::load_game(config.save_file(), &play.game.settings) {
+            Ok(mut game) => {
+                StateCore::apply_dialogues(&mut game, creatures);
+                play.replace(game, config);
+                app.go_to(Screen::Playing);
+            }
+            Err(error) => {
+                Diagnostics::log(
+                    Level::Warn,
+                    TARGET_GAME,
+                    "could not load save",
+                    &[("error", &error)],
+                );
+                app.notice = Some(format!("Could not load the save: {}", error));
+            }
+        },
+        MenuAction::Resume => app.go_to(Screen::Playing),
+        MenuAction::Options => app.go_to(Screen::Options),
+        MenuAction::ToggleAnimations => {
+            StateCore::update_hud(&mut play.hud, &mut play.game, Command::ToggleAnimations)
+        }
+        MenuAction::ToggleDebug => {
+            StateCore::update_hud(&mut play.hud, &mut play.game, Command::ToggleDebug)
+        }
+        MenuAction::ToggleMinimap => {
+            StateCore::update_hud(&mut play.hud, &mut play.game, Command::ToggleMinimap)
+        }
+        MenuAction::Change(option, step) => {
+            config.user.change(option, step);
+            play.apply(config);
+
+            if let Err(error) = store_user_settings(&config.user) {
+                app.notice = Some(error);
+            }
+        }
+        MenuAction::Back => app.go_to(app.options_from),
+        MenuAction::SaveAndQuit => match save(play, config) {
+            Ok(()) => app.go_to(Screen::Title),
+            Err(error) => app.notice = Some(error),
+        },
+        MenuAction::Quit => app.quit = true,
+    }
+}
+
+// raylib numbers letters and digits after their ascii code, the other keys the game has a use
+// for are picked one by one
+fn get_pressed_key(d: &RaylibDrawHandle) -> Option<Key> {
+    let named = [
+        (KeyboardKey::KEY_LEFT, Key::Left),
+        (KeyboardKey::KEY_DOWN, Key::Down),
+        (KeyboardKey::KEY_UP, Key::Up),
+        (KeyboardKey::KEY_RIGHT, Key::Right),
+        (KeyboardKey::KEY_PAGE_UP, Key::PageUp),
+        (KeyboardKey::KEY_PAGE_DOWN, Key::PageDown),
+        (KeyboardKey::KEY_F3, Key::F3),
+        (KeyboardKey::KEY_F4, Key::F4),
+        (KeyboardKey::KEY_ENTER, Key::Enter),
+        (KeyboardKey::KEY_KP_ENTER, Key::Enter),
+        (KeyboardKey::KEY_ESCAPE, Key::Escape),
+    ];
+    let typed = ('A'..='Z')
+        .chain('0'..='9')
+        .chain(['.'])
+        .filter_map(|character| {
+            Some((
+                key_from_i32(character as i32)?,
+                StateCore::get_key_for_char(character)?,
+            ))
+        });
+
+    named
+        .into_iter()
+        .chain(typed)
+        .find(|(raylib_key, _)| d.is_key_pressed(*raylib_key))
+        .map(|(_, key)| key)
+}
+
+// plays whatever was asked for without a window, then prints how it ended
+fn run_headless(play: &mut Play, tileset: &Tileset) {
+    while let Some(command) = play.replaying.pop_front() {
+        play.game = StateCore::update_game_states(&play.game, &play.game.settings, command);
+    }
+
+    let game = &play.game;
+
+    println!(
+        "{}",
+        AsciiRender::render_to_string(&Render::draw_list(game, tileset))
+    );
+
+    if let Some(replay) = play.replay.as_ref() {
+        println!("seed: {}", replay.seed);
+    }
+    println!("turn: {}", game.turn);
+
+    match StateHelpers::get_player(game) {
+        Some(player) => println!(
+            "player: {}/{} health at {},{}",
+            player.stats.health, player.stats.max_health, player.pos.x, player.pos.y
+        ),
+        None => println!("player: dead"),
+    }
+
+    let first = game
+        .messages
+        .len()
+        .saturating_sub(game.settings.hud.log_lines as usize);
+
+    for message in game.messages[first..].iter() {
+        println!("[{}] {}", message.turn, message.text);
     }
 }
 
 fn main() {
-    let settings: Settings = StateHelpers::get_settings(800, 800, 20, 20, 26);
-    let entities: Vec<Entity> = StateCore::get_starting_entities(&settings);
-    let buildings: Vec<Entity> = StateCore::get_starting_buildings(&settings);
-
-    let mut game = Game {
-        entities: entities,
-        settings: settings,
-        buildings: buildings,
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match Cli::parse_args(&args) {
+        Ok(options) if options.help => {
+            print!("{}", Cli::USAGE);
+            return;
+        }
+        Ok(options) => options,
+        Err(error) => {
+            eprint!("{}\n\n{}", error, Cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    Diagnostics::init_from_env();
+
+    let user = UserSettingsFile::load_or_default(&UserSettingsFile::settings_path());
+
+    let mut tileset: Tileset = match TilesetContent::load_tileset(TILESET_FILE) {
+        Ok(tileset) => tileset,
+        Err(error) => {
+            Diagnostics::log(
+                Level::Error,
+                TARGET_GAME,
+                "could not load tileset",
+                &[("error", &error)],
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let dialogues = match Dialogues::load_dialogues(DIALOGUES_FILE) {
+        Ok(dialogues) => dialogues,
+        Err(error) => {
+            Diagnostics::log(
+                Level::Error,
+                TARGET_GAME,
+                "could not load dialogues",
+                &[("error", &error)],
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let mut creatures: Vec<CreatureTemplate> =
+        match Creatures::load_creatures(CREATURES_FILE, &tileset, &dialogues) {
+            Ok(creatures) => creatures,
+            Err(error) => {
+                Diagnostics::log(
+                    Level::Error,
+                    TARGET_GAME,
+                    "could not load creatures",
+                    &[("error", &error)],
+                );
+                std::process::exit(1);
+            }
+        };
+
+    let mut config = Config {
+        user,
+        options,
+        tile_size: tile_size(&tileset),
     };
+    let settings = config.new_game_settings();
+
+    if let Err(error) = Cli::check_settings(&settings) {
+        eprint!("{}\n\n{}", error, Cli::USAGE);
+        std::process::exit(2);
+    }
+
+    // the title screen needs something behind it until a game gets started or loaded
+    let mut play = Play::new(StateCore::new_game(settings, &creatures));
+    play.hud.minimap = config.user.minimap;
+    let mut app = App::new();
+
+    // straight into a game when the command line asks for one
+    if let Some(path) = config.options.replay.clone() {
+        match ReplayFile::load_replay(&path) {
+            Ok(replay) => play.watch(replay, &config, &creatures),
+            Err(error) => {
+                eprintln!("could not load the replay: {}", error);
+                std::process::exit(1);
+            }
+        }
+        app.go_to(Screen::Playing);
+    } else if config.options.load.is_some() {
+        match Save::load_game(config.save_file(), &settings) {
+            Ok(mut game) => {
+                StateCore::apply_dialogues(&mut game, &creatures);
+                play.replace(game, &config);
+            }
+            Err(error) => {
+                eprintln!("could not load the save: {}", error);
+                std::process::exit(1);
+            }
+        }
+        app.go_to(Screen::Playing);
+    } else if config.options.headless {
+        let seed = config
+            .options
+            .seed
+            .take()
+            .unwrap_or_else(Random::random_seed);
+
+        play.start(seed, &config, &creatures);
+    }
+
+    if config.options.headless {
+        run_headless(&mut play, &tileset);
+        return;
+    }
+
+    let settings = play.game.settings;
 
     let (mut rl, thread) = raylib::init()
-        .size(settings.window.width, settings.window.height)
+        .size(
+            settings.window.width,
+            settings.window.height + settings.hud.log_height,
+        )
         .title("Poorguelike")
         .build();
 
-    let tileset_entities = rl.load_texture(&thread, "./tileset_entities.png").unwrap();
-    let tileset_terrain = rl.load_texture(&thread, "./tileset_terrain.png").unwrap();
+    // effects last a number of frames, keep those frames the same length everywhere
+    rl.set_target_fps(60);
+
+    // escape pauses the game instead of closing the window
+    rl.set_exit_key(None);
+
+    let mut textures = DrawGame::load_textures(&mut rl, &thread, &tileset).unwrap();
+
+    let mut terrain_cache = TerrainCache::new();
+
+    let mut content_watcher = ContentWatcher::new(&[TILESET_FILE, CREATURES_FILE, DIALOGUES_FILE]);
+
+    while !rl.window_should_close() && !app.quit {
+        // the window follows the options, and the size of a loaded map when tiles are scaled
+        let settings = play.game.settings;
+        let window_size = (
+            settings.window.width,
+            settings.window.height + settings.hud.log_height,
+        );
+
+        if (rl.get_screen_width(), rl.get_screen_height()) != window_size {
+            rl.set_window_size(window_size.0, window_size.1);
+        }
+
+        let changed = content_watcher.poll();
+
+        if !changed.is_empty() {
+            reload_content(
+                &mut rl,
+                &thread,
+                &changed,
+                &mut play.game,
+                &mut tileset,
+                &mut textures,
+                &mut creatures,
+            );
+
+            // sprites may have moved around in the atlases
+            terrain_cache.invalidate();
+            config.tile_size = tile_size(&tileset);
+        }
+
+        if let Err(error) = DrawGame::update_terrain_cache(
+            &mut rl,
+            &thread,
+            &mut terrain_cache,
+            &play.game,
+            &tileset,
+            &textures,
+        ) {
+            Diagnostics::log(
+                Level::Error,
+                TARGET_GAME,
+                "could not draw terrain",
+                &[("error", &error)],
+            );
+            std::process::exit(1);
+        }
 
-    while !rl.window_should_close() {
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::BLACK);
 
-        let interaction = StateHelpers::user_interacted(&mut d);
-        let interacted = match interaction {
-            Some(key) => true,
-            None => false,
+        let command = match get_pressed_key(&d) {
+            Some(key) => StateCore::get_command_for_key(&key, play.game.settings.keys),
+            None => None,
         };
 
-        let key_pressed = match interaction {
-            Some(key) => key,
-            None => KeyboardKey::KEY_NULL,
-        };
+        let has_save = Path::new(config.save_file()).exists();
+        let items = app.menu(&play.game, &play.hud, &config.user, has_save);
+        app.settle_selection(&items);
+
+        match app.screen {
+            Screen::Playing => update_playing(&d, command, &mut play, &mut app, &config, &tileset),
+            _ => {
+                let action = command.and_then(|command| app.handle_menu(command, &items));
+
+                if let Some(action) = action {
+                    run_menu_action(action, &mut app, &mut play, &mut config, &creatures);
 
-        if interacted {
-            let updated_game: Game =
-                StateCore::update_game_states(&mut d, &game, &settings, key_pressed);
+                    // the palette tints the terrain, and a new or loaded game brings its own
+                    // map, which may well be at the same terrain revision as the last one
+                    if matches!(
+                        action,
+                        MenuAction::Change(..) | MenuAction::NewGame | MenuAction::Continue
+                    ) {
+                        terrain_cache.invalidate();
+                    }
+                }
+            }
+        }
+
+        // animations and the minimap also have keys of their own, the file follows them
+        let toggles = (play.game.settings.animations, play.hud.minimap);
+
+        if (config.user.animations, config.user.minimap) != toggles {
+            (config.user.animations, config.user.minimap) = toggles;
+            let _ = store_user_settings(&config.user);
+        }
 
+        // the game stays in the background of every screen once there is one
+        if app.in_game() || app.screen == Screen::GameOver {
             DrawGame::frame(
                 &mut d,
-                &updated_game,
-                &settings,
-                &tileset_terrain,
-                &tileset_entities,
+                &play.game,
+                &play.hud,
+                &tileset,
+                &textures,
+                &terrain_cache,
+                &play.animator,
+                &play.effects,
             );
 
-            game = updated_game;
+            play.effects.tick();
+        }
 
-            debug(&game)
-        } else {
-            DrawGame::frame(
+        if app.screen != Screen::Playing {
+            let has_save = Path::new(config.save_file()).exists();
+            let items = app.menu(&play.game, &play.hud, &config.user, has_save);
+            app.settle_selection(&items);
+
+            DrawGame::menu(
                 &mut d,
-                &game,
-                &settings,
-                &tileset_terrain,
-                &tileset_entities,
+                &play.game.settings,
+                &app.title(&play.game),
+                &items,
+                app.selected,
+                app.notice.as_deref(),
+            );
+        }
+    }
+
+    // closing the window mid game keeps it for later
+    if app.in_game() && StateHelpers::get_player(&play.game).is_some() {
+        if let Err(error) = save(&play, &config) {
+            Diagnostics::log(
+                Level::Error,
+                TARGET_GAME,
+                "could not save on exit",
+                &[("error", &error)],
             );
         }
     }
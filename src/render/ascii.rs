@@ -0,0 +1,197 @@
+use crate::color::Color;
+use crate::render::{DrawList, Renderer};
+
+// Draws the map as one character per tile, plain for snapshot tests or with ansi colours
+// for terminals
+pub struct AsciiRenderer {
+    pub colored: bool,
+    columns: i32,
+    rows: i32,
+    cells: Vec<(char, Color)>,
+}
+
+impl AsciiRenderer {
+    pub fn new(colored: bool) -> AsciiRenderer {
+        AsciiRenderer {
+            colored,
+            columns: 0,
+            rows: 0,
+            cells: Vec::new(),
+        }
+    }
+
+    // one line per row, without a trailing new line
+    pub fn output(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+
+        for row in self.cells.chunks(self.columns.max(1) as usize) {
+            let mut line = String::new();
+            let mut current: Option<Color> = None;
+
+            for (glyph, color) in row {
+                if self.colored && *glyph != ' ' && current != Some(*color) {
+                    line.push_str(&format!("\x1b[38;2;{};{};{}m", color.r, color.g, color.b));
+                    current = Some(*color);
+                }
+
+                line.push(*glyph);
+            }
+
+            if current.is_some() {
+                line.push_str("\x1b[0m");
+            }
+
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl Renderer for AsciiRenderer {
+    fn render(&mut self, list: &DrawList) {
+        self.columns = list.columns;
+        self.rows = list.rows;
+        self.cells = vec![(' ', Color::BLACK); (list.columns * list.rows).max(0) as usize];
+
+        for item in list.items.iter() {
+            if item.pos.x < 0
+                || item.pos.y < 0
+                || item.pos.x >= self.columns
+                || item.pos.y >= self.rows
+            {
+                continue;
+            }
+
+            self.cells[(item.pos.y * self.columns + item.pos.x) as usize] =
+                (item.glyph, item.color);
+        }
+    }
+}
+
+pub fn render_to_string(list: &DrawList) -> String {
+    let mut renderer = AsciiRenderer::new(false);
+    renderer.render(list);
+    renderer.output()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::parser as Parser;
+    use crate::content::tileset::{self as TilesetContent, Tileset};
+    use crate::render as Render;
+    use crate::state::domain::{EntityAi, EntityMode, EntityRelationship, Game, Position};
+    use crate::state::helpers as StateHelpers;
+    use crate::state::test_support as TestSupport;
+
+    const TILESET: &str = "
+[atlas terrain]
+image = terrain.png
+tile_size = 16
+
+[sprite dirt]
+atlas = terrain
+glyph = .
+tile = 0, 0
+
+[sprite grass]
+atlas = terrain
+glyph = .
+tile = 1, 0
+
+[sprite brick_wall]
+atlas = terrain
+glyph = #
+tile = 2, 0
+
+[sprite ghost]
+atlas = terrain
+glyph = G
+tile = 3, 0
+
+[sprite nameless]
+atlas = terrain
+tile = 4, 0
+";
+
+    fn get_tileset() -> Tileset {
+        TilesetContent::parse_tileset("test", &Parser::parse("test", TILESET).unwrap()).unwrap()
+    }
+
+    // a walled 6x4 room with a ghost and something the tileset has no glyph for, all of it in
+    // sight as there's no player around to see it
+    fn get_room_game() -> Game {
+        let settings = StateHelpers::get_settings(160, 240, 4, 6, 26);
+        let buildings = TestSupport::tiles(&settings, |x, y| {
+            match x == 0 || y == 0 || x == 5 || y == 3 {
+                true => EntityMode::BrickWall,
+                false => EntityMode::Ground,
+            }
+        });
+        let ghost = TestSupport::template("ghost", EntityAi::Wander, EntityRelationship::Foe);
+        let nameless = TestSupport::template("nameless", EntityAi::Wander, EntityRelationship::Foe);
+
+        let mut game = TestSupport::game(
+            settings,
+            buildings,
+            vec![
+                StateHelpers::gen_creature(&ghost, Position { x: 1, y: 1 }),
+                StateHelpers::gen_creature(&nameless, Position { x: 4, y: 2 }),
+            ],
+        );
+        game.vision.visible = vec![true; 24];
+        game.vision.explored = vec![true; 24];
+        game
+    }
+
+    #[test]
+    fn renders_the_map_as_text() {
+        let game = get_room_game();
+        let tileset = get_tileset();
+
+        assert_eq!(
+            render_to_string(&Render::draw_list(&game, &tileset)),
+            "######\n\
+             #G...#\n\
+             #...?#\n\
+             ######"
+        );
+    }
+
+    #[test]
+    fn colours_only_change_when_needed() {
+        let game = get_room_game();
+        let tileset = get_tileset();
+
+        let mut renderer = AsciiRenderer::new(true);
+        renderer.render(&Render::draw_list(&game, &tileset));
+
+        let first_line = renderer.output().lines().next().unwrap().to_string();
+        assert_eq!(first_line, "\x1b[38;2;204;204;204m######\x1b[0m");
+    }
+
+    #[test]
+    fn leaves_out_what_was_never_seen_and_creatures_out_of_sight() {
+        let mut game = get_room_game();
+        let tileset = get_tileset();
+
+        // the two left columns are in sight, the next two only remembered
+        for y in 0..4 {
+            for x in 0..6 {
+                let index = (y * 6 + x) as usize;
+                game.vision.visible[index] = x < 2;
+                game.vision.explored[index] = x < 4;
+            }
+        }
+        game.entities[1].pos = Position { x: 3, y: 2 };
+
+        assert_eq!(
+            render_to_string(&Render::draw_list(&game, &tileset)),
+            "####  \n\
+             #G..  \n\
+             #...  \n\
+             ####  "
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::color::Color;
+use crate::content::tileset::Tileset;
+use crate::state::domain::{Entity, EntityMode, Game, Palette, Position};
+use crate::state::traps as Traps;
+use uuid::Uuid;
+
+pub mod ascii;
+
+// What the map looks like for a frame, without saying how it gets shown: the raylib renderer
+// turns it into sprites from the tileset, the ascii one into characters
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Terrain,
+    Item,
+    Actor,
+}
+
+#[derive(Debug, Clone)]
+pub struct DrawItem<'a> {
+    pub id: Uuid,
+    pub layer: Layer,
+    pub sprite: &'a str,
+    pub variant: u32,
+    pub glyph: char,
+    pub color: Color,
+    pub pos: Position,
+    // how far from pos it is drawn, in tiles, for renderers that can show in-between tiles
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+// items are ordered back to front, later ones cover earlier ones on the same tile
+#[derive(Debug, Clone)]
+pub struct DrawList<'a> {
+    pub columns: i32,
+    pub rows: i32,
+    pub items: Vec<DrawItem<'a>>,
+}
+
+// A renderer may draw the Terrain layer from somewhere else than the list: the raylib one
+// skips those items and draws its terrain cache instead, so terrain changes only show up there
+// once Game::terrain_revision is bumped
+pub trait Renderer {
+    fn render(&mut self, list: &DrawList);
+}
+
+// every colour that doesn't come from the content files, so the palette option covers them
+#[derive(Debug, Clone, Copy)]
+pub struct PaletteColors {
+    pub wall: Color,
+    pub ground: Color,
+    pub water: Color,
+    pub deep_water: Color,
+    pub lava: Color,
+    pub grid: Color,
+    pub text: Color,
+    pub warning: Color,
+    pub highlight: Color,
+    pub look: Color,
+    // damage taken by the player, and dealt by it
+    pub hurt: Color,
+    pub hit: Color,
+    pub minimap_wall: Color,
+    pub minimap_floor: Color,
+    // behind the message log, and around it and the minimap
+    pub panel: Color,
+    pub border: Color,
+}
+
+fn hex(color: &str) -> Color {
+    Color::from_hex(color).unwrap()
+}
+
+pub fn palette_colors(palette: Palette) -> PaletteColors {
+    match palette {
+        Palette::Default => PaletteColors {
+            wall: hex("CCCCCC"),
+            ground: hex("AAAAAA"),
+            water: hex("7FC8F0"),
+            deep_water: hex("3A6FC0"),
+            lava: hex("FF6A2A"),
+            grid: hex("222222"),
+            text: Color::WHITE,
+            warning: Color::ORANGE,
+            highlight: Color::YELLOW,
+            look: Color::SKYBLUE,
+            hurt: Color::RED,
+            hit: Color::YELLOW,
+            minimap_wall: hex("AAAAAA"),
+            minimap_floor: hex("555555"),
+            panel: hex("111111"),
+            border: hex("444444"),
+        },
+        Palette::HighContrast => PaletteColors {
+            wall: hex("FFFFFF"),
+            ground: hex("777777"),
+            water: hex("00FFFF"),
+            deep_water: hex("0000FF"),
+            lava: hex("FF0000"),
+            grid: hex("444444"),
+            text: Color::WHITE,
+            warning: hex("FFFF00"),
+            highlight: hex("00FFFF"),
+            look: hex("00FF00"),
+            hurt: hex("FF0000"),
+            hit: hex("FFFF00"),
+            minimap_wall: hex("FFFFFF"),
+            minimap_floor: hex("555555"),
+            panel: Color::BLACK,
+            border: hex("FFFFFF"),
+        },
+        // picked from the Okabe-Ito set, told apart with every common kind of colour blindness
+        Palette::Colorblind => PaletteColors {
+            wall: hex("CCCCCC"),
+            ground: hex("AAAAAA"),
+            water: hex("56B4E9"),
+            deep_water: hex("0072B2"),
+            lava: hex("D55E00"),
+            grid: hex("222222"),
+            text: Color::WHITE,
+            warning: hex("E69F00"),
+            highlight: hex("F0E442"),
+            look: hex("56B4E9"),
+            hurt: hex("D55E00"),
+            hit: hex("F0E442"),
+            minimap_wall: hex("AAAAAA"),
+            minimap_floor: hex("555555"),
+            panel: hex("111111"),
+            border: hex("444444"),
+        },
+    }
+}
+
+fn terrain_color(mode: EntityMode, colors: &PaletteColors) -> Color {
+    match mode {
+        EntityMode::BrickWall | EntityMode::Door | EntityMode::Rubble => colors.wall,
+        EntityMode::Ground => colors.ground,
+        EntityMode::ShallowWater => colors.water,
+        EntityMode::DeepWater => colors.deep_water,
+        EntityMode::Lava => colors.lava,
+        _ => unreachable!(),
+    }
+}
+
+fn draw_item<'a>(
+    entity: &'a Entity,
+    layer: Layer,
+    color: Color,
+    tileset: &Tileset,
+) -> DrawItem<'a> {
+    DrawItem {
+        id: entity.id,
+        layer,
+        sprite: &entity.characteristics.face,
+        variant: entity.characteristics.variant,
+        glyph: tileset.glyph(&entity.characteristics.face),
+        color,
+        pos: entity.pos,
+        offset_x: 0.0,
+        offset_y: 0.0,
+    }
+}
+
+// the map, items and traps show up once explored, creatures only while in sight
+pub fn terrain_items<'a>(game: &'a Game, tileset: &Tileset) -> Vec<DrawItem<'a>> {
+    let colors = palette_colors(game.settings.palette);
+
+    game.buildings
+        .iter()
+        .filter(|building| game.vision.is_explored(&building.pos))
+        .map(|building| {
+            draw_item(
+                building,
+                Layer::Terrain,
+                terrain_color(building.mode, &colors),
+                tileset,
+            )
+        })
+        .collect()
+}
+
+// only the ones the player found, tinted like warnings
+pub fn trap_items<'a>(game: &'a Game, tileset: &Tileset) -> Vec<DrawItem<'a>> {
+    let colors = palette_colors(game.settings.palette);
+
+    game.traps
+        .iter()
+        .filter(|trap| !trap.hidden && game.vision.is_explored(&trap.pos))
+        .map(|trap| DrawItem {
+            id: trap.id,
+            layer: Layer::Item,
+            sprite: Traps::get_face(trap.kind),
+            variant: 0,
+            glyph: tileset.glyph(Traps::get_face(trap.kind)),
+            color: colors.warning,
+            pos: trap.pos,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        })
+        .collect()
+}
+
+pub fn item_items<'a>(game: &'a Game, tileset: &Tileset) -> Vec<DrawItem<'a>> {
+    game.items
+        .iter()
+        .filter(|item| game.vision.is_explored(&item.pos))
+        .map(|item| draw_item(item, Layer::Item, item.characteristics.color, tileset))
+        .collect()
+}
+
+pub fn actor_item<'a>(entity: &'a Entity, tileset: &Tileset) -> DrawItem<'a> {
+    draw_item(entity, Layer::Actor, entity.characteristics.color, tileset)
+}
+
+pub fn actor_items<'a>(game: &'a Game, tileset: &Tileset) -> Vec<DrawItem<'a>> {
+    game.entities
+        .iter()
+        .filter(|entity| game.vision.is_visible(&entity.pos))
+        .map(|entity| actor_item(entity, tileset))
+        .collect()
+}
+
+pub fn draw_list<'a>(game: &'a Game, tileset: &Tileset) -> DrawList<'a> {
+    let mut items = terrain_items(game, tileset);
+    items.append(&mut trap_items(game, tileset));
+    items.append(&mut item_items(game, tileset));
+    items.append(&mut actor_items(game, tileset));
+
+    DrawList {
+        columns: game.settings.graphic.columns,
+        rows: game.settings.graphic.rows,
+        items,
+    }
+}
@@ -0,0 +1,284 @@
+use crate::content::creatures::CreatureTemplate;
+use crate::content::parser as Parser;
+use crate::content::ContentError;
+use crate::state::core as StateCore;
+use crate::state::domain::{Command, Game, Generator, Settings, WorldSettings};
+use crate::state::helpers as StateHelpers;
+use crate::state::random as Random;
+use std::fs;
+
+// A game as it was started (seed, map and spawns) and every command played since. Starting
+// it again and feeding it the same commands gives the same game back, as long as the
+// content files didn't change in between.
+
+pub const REPLAY_FILE: &str = "./poorguelike.replay";
+
+const REPLAY_VERSION: i32 = 6;
+
+const GENERATORS: [Generator; 2] = [Generator::Arena, Generator::Rooms];
+// only what reaches update_game_states is recorded, the number keys, enter and escape get
+// there while talking (see state::dialogue)
+const COMMANDS: [Command; 20] = [
+    Command::MoveLeft,
+    Command::MoveRight,
+    Command::MoveUp,
+    Command::MoveDown,
+    Command::Wait,
+    Command::Door,
+    Command::PickUp,
+    Command::Search,
+    Command::Count(0),
+    Command::Count(1),
+    Command::Count(2),
+    Command::Count(3),
+    Command::Count(4),
+    Command::Count(5),
+    Command::Count(6),
+    Command::Count(7),
+    Command::Count(8),
+    Command::Count(9),
+    Command::Confirm,
+    Command::Cancel,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+    pub seed: u64,
+    pub columns: i32,
+    pub rows: i32,
+    pub world: WorldSettings,
+    pub commands: Vec<Command>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, settings: &Settings) -> Replay {
+        Replay {
+            seed,
+            columns: settings.graphic.columns,
+            rows: settings.graphic.rows,
+            world: settings.world,
+            commands: Vec::new(),
+        }
+    }
+
+    // the recorded map and spawns, everything else comes from the given settings
+    pub fn settings(&self, settings: &Settings) -> Settings {
+        Settings {
+            world: self.world,
+            ..StateHelpers::resize_map(settings, self.rows, self.columns)
+        }
+    }
+
+    // the game as it was before the first command
+    pub fn start(&self, settings: &Settings, creatures: &[CreatureTemplate]) -> Game {
+        Random::seed(self.seed);
+
+        StateCore::new_game(self.settings(settings), creatures)
+    }
+
+    // the game as it was after the last command
+    pub fn play(&self, settings: &Settings, creatures: &[CreatureTemplate]) -> Game {
+        let mut game = self.start(settings, creatures);
+
+        for command in self.commands.iter() {
+            game = StateCore::update_game_states(&game, &game.settings, *command);
+        }
+
+        game
+    }
+}
+
+pub fn save_replay(path: &str, replay: &Replay) -> Result<(), String> {
+    let mut text = String::from("# poorguelike replay, written by the game\n\n[replay]\n");
+
+    text.push_str(&format!("version = {}\n", REPLAY_VERSION));
+    text.push_str(&format!("seed = {}\n", replay.seed));
+    text.push_str(&format!("columns = {}\n", replay.columns));
+    text.push_str(&format!("rows = {}\n", replay.rows));
+    text.push_str(&format!("generator = {:?}\n", replay.world.generator));
+    text.push_str(&format!("npcs = {}\n", replay.world.npcs));
+    text.push_str(&format!("mobs = {}\n", replay.world.mobs));
+
+    for command in replay.commands.iter() {
+        text.push_str(&format!("command = {:?}\n", command));
+    }
+
+    fs::write(path, text).map_err(|error| format!("could not write {}: {}", path, error))
+}
+
+pub fn load_replay(path: &str) -> Result<Replay, ContentError> {
+    let sections = Parser::read_file(path)?;
+
+    let section = sections
+        .iter()
+        .find(|section| section.name == "replay")
+        .ok_or_else(|| ContentError {
+            file: path.to_string(),
+            line: 0,
+            entry: None,
+            message: "missing [replay]".to_string(),
+        })?;
+
+    let version = section.require_int("version")?;
+    if version != REPLAY_VERSION {
+        return Err(section.error(
+            section.require("version")?.line,
+            format!("unsupported replay version {}", version),
+        ));
+    }
+
+    Ok(Replay {
+        seed: section.require_parsed("seed")?,
+        columns: section.require_int("columns")?,
+        rows: section.require_int("rows")?,
+        world: WorldSettings {
+            generator: section.require_named("generator", &GENERATORS)?,
+            npcs: section.require_int("npcs")?,
+            mobs: section.require_int("mobs")?,
+        },
+        commands: section
+            .get_all("command")
+            .iter()
+            .map(|entry| section.named(entry, &COMMANDS))
+            .collect::<Result<Vec<Command>, ContentError>>()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::dialogues as Dialogues;
+    use crate::state::domain::{EntityAi, EntityRelationship};
+    use crate::state::test_support as TestSupport;
+    use std::env;
+    use uuid::Uuid;
+
+    fn save_and_load(replay: &Replay) -> Replay {
+        let path = env::temp_dir().join(format!("poorguelike-{}.replay", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        save_replay(path, replay).unwrap();
+        let loaded = load_replay(path);
+        let _ = fs::remove_file(path);
+
+        loaded.unwrap()
+    }
+
+    #[test]
+    fn a_replay_plays_back_the_same_game() {
+        let creatures = [
+            TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral),
+            TestSupport::template("ghost", EntityAi::Wander, EntityRelationship::Foe),
+        ];
+        let settings = Settings {
+            world: WorldSettings {
+                generator: Generator::Rooms,
+                npcs: 0,
+                mobs: 6,
+            },
+            ..StateHelpers::get_settings(800, 800, 16, 24, 20)
+        };
+
+        let mut replay = Replay::new(1234, &settings);
+        replay.commands = [Command::MoveRight, Command::MoveDown, Command::Wait]
+            .iter()
+            .cycle()
+            .take(30)
+            .copied()
+            .collect();
+
+        let loaded = save_and_load(&replay);
+        assert_eq!(loaded, replay);
+
+        let first = replay.play(&settings, &creatures);
+        let second = loaded.play(
+            &StateHelpers::get_settings(800, 800, 20, 20, 20),
+            &creatures,
+        );
+
+        assert_eq!(second.turn, 30);
+        assert_eq!(second.settings.graphic.columns, 24);
+
+        let summary = |game: &Game| -> Vec<(String, i32, i32, i32)> {
+            game.entities
+                .iter()
+                .map(|entity| {
+                    (
+                        entity.template.to_owned(),
+                        entity.pos.x,
+                        entity.pos.y,
+                        entity.stats.health,
+                    )
+                })
+                .collect()
+        };
+
+        assert_eq!(summary(&first), summary(&second));
+        assert_eq!(
+            first.messages.iter().map(|m| &m.text).collect::<Vec<_>>(),
+            second.messages.iter().map(|m| &m.text).collect::<Vec<_>>()
+        );
+
+        // a conversation: in a 2x1 arena the hermit can only stand right next to the player
+        let sections = Parser::parse(
+            "dialogues.cfg",
+            "
+[dialogue hermit]
+start = hello
+
+[line hello]
+text = Hm?
+choice = ask_tool
+choice = bye
+
+[choice ask_tool]
+text = Got a pickaxe?
+do = give pickaxe
+next = given
+
+[line given]
+text = Take it.
+
+[choice bye]
+text = Bye.
+",
+        )
+        .unwrap();
+        let mut hermit =
+            TestSupport::template("hermit", EntityAi::Wander, EntityRelationship::Friendly);
+        hermit.dialogue = Dialogues::parse_dialogues(&sections).unwrap().pop();
+        let creatures = [creatures[0].clone(), hermit];
+        let settings = Settings {
+            world: WorldSettings {
+                generator: Generator::Arena,
+                npcs: 1,
+                mobs: 0,
+            },
+            ..StateHelpers::get_settings(800, 800, 3, 4, 20)
+        };
+
+        let mut replay = Replay::new(26, &settings);
+        replay.commands = vec![
+            Command::MoveRight,
+            Command::Count(1),
+            Command::Confirm,
+            Command::MoveRight,
+            Command::Cancel,
+            Command::MoveRight,
+            Command::Count(2),
+            Command::Wait,
+        ];
+
+        let loaded = save_and_load(&replay);
+        assert_eq!(loaded, replay);
+
+        let game = loaded.play(&settings, &creatures);
+        assert_eq!(game.inventory[0].name, "pickaxe");
+        assert_eq!(game.conversation, None);
+        assert_eq!(game.turn, 1);
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "The hermit says: \"Hm?\""
+        );
+    }
+}
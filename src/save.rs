@@ -0,0 +1,409 @@
+use crate::content::parser::{self as Parser, Section};
+use crate::content::ContentError;
+use crate::state::domain::{
+    Alarm, Entity, EntityAi, EntityCharacteristics, EntityMode, EntityRelationship, EntityStats,
+    Game, Material, Message, MessageKind, Position, Settings, Trap, Vision,
+};
+use crate::state::fov as FieldOfView;
+use crate::state::helpers as StateHelpers;
+use crate::state::traps as Traps;
+use std::fs;
+use uuid::Uuid;
+
+// A game in progress, written with the same "[section]" and "key = value" format as the
+// content files so it can be read back with the same parser (and by a human, if needed)
+
+pub const SAVE_FILE: &str = "./poorguelike.sav";
+
+// bumped whenever what gets written changes, older saves are refused rather than guessed at
+const SAVE_VERSION: i32 = 5;
+
+// each kind of section only takes the modes that belong there, the map and the renderer count
+// on buildings being terrain and on entities being creatures
+const TERRAIN_MODES: [EntityMode; 7] = [
+    EntityMode::BrickWall,
+    EntityMode::Ground,
+    EntityMode::Door,
+    EntityMode::ShallowWater,
+    EntityMode::Rubble,
+    EntityMode::DeepWater,
+    EntityMode::Lava,
+];
+const CREATURE_MODES: [EntityMode; 3] = [EntityMode::Player, EntityMode::NPC, EntityMode::Mob];
+const ITEM_MODES: [EntityMode; 1] = [EntityMode::Item];
+const RELATIONSHIPS: [EntityRelationship; 4] = [
+    EntityRelationship::Foe,
+    EntityRelationship::Friendly,
+    EntityRelationship::Neutral,
+    EntityRelationship::None,
+];
+const AIS: [EntityAi; 3] = [EntityAi::None, EntityAi::Player, EntityAi::Wander];
+const MATERIALS: [Material; 2] = [Material::Brick, Material::Bedrock];
+const MESSAGE_KINDS: [MessageKind; 2] = [MessageKind::Info, MessageKind::Warning];
+
+fn write_entity(text: &mut String, kind: &str, entity: &Entity) {
+    let color = entity.characteristics.color;
+
+    text.push_str(&format!("\n[{} {}]\n", kind, entity.id));
+    text.push_str(&format!("name = {}\n", entity.name));
+    text.push_str(&format!("template = {}\n", entity.template));
+    text.push_str(&format!("mode = {:?}\n", entity.mode));
+    text.push_str(&format!("relationship = {:?}\n", entity.relationship));
+    text.push_str(&format!("ai = {:?}\n", entity.ai));
+    text.push_str(&format!("face = {}\n", entity.characteristics.face));
+    text.push_str(&format!("variant = {}\n", entity.characteristics.variant));
+    text.push_str(&format!(
+        "color = {:02X}{:02X}{:02X}\n",
+        color.r, color.g, color.b
+    ));
+    text.push_str(&format!("walkable = {}\n", entity.characteristics.walkable));
+    if let Some(lock) = &entity.characteristics.lock {
+        text.push_str(&format!("lock = {}\n", lock));
+    }
+    text.push_str(&format!(
+        "opens_doors = {}\n",
+        entity.characteristics.opens_doors
+    ));
+    text.push_str(&format!("swims = {}\n", entity.characteristics.swims));
+    text.push_str(&format!("digs = {}\n", entity.characteristics.digs));
+    if let Some(material) = entity.characteristics.material {
+        text.push_str(&format!("material = {:?}\n", material));
+    }
+    text.push_str(&format!("health = {}\n", entity.stats.health));
+    text.push_str(&format!("max_health = {}\n", entity.stats.max_health));
+    text.push_str(&format!("attack = {}\n", entity.stats.attack));
+    text.push_str(&format!("x = {}\ny = {}\n", entity.pos.x, entity.pos.y));
+    text.push_str(&format!("busy = {}\n", entity.busy));
+}
+
+pub fn save_game(path: &str, game: &Game) -> Result<(), String> {
+    let mut text = String::from("# poorguelike save, written by the game\n\n[game]\n");
+
+    text.push_str(&format!("version = {}\n", SAVE_VERSION));
+    text.push_str(&format!("turn = {}\n", game.turn));
+    text.push_str(&format!("terrain_revision = {}\n", game.terrain_revision));
+    text.push_str(&format!("columns = {}\n", game.settings.graphic.columns));
+    text.push_str(&format!("rows = {}\n", game.settings.graphic.rows));
+
+    // one line of 0 and 1 per map row
+    text.push_str("\n[vision]\n");
+    for row in game
+        .vision
+        .explored
+        .chunks(game.vision.columns.max(1) as usize)
+    {
+        let flags: String = row
+            .iter()
+            .map(|explored| if *explored { '1' } else { '0' })
+            .collect();
+        text.push_str(&format!("explored = {}\n", flags));
+    }
+
+    for entity in game.entities.iter() {
+        write_entity(&mut text, "entity", entity);
+    }
+
+    for building in game.buildings.iter() {
+        write_entity(&mut text, "building", building);
+    }
+
+    for item in game.items.iter() {
+        write_entity(&mut text, "item", item);
+    }
+
+    for item in game.inventory.iter() {
+        write_entity(&mut text, "carried", item);
+    }
+
+    // who knows about it, one id per line
+    for trap in game.traps.iter() {
+        text.push_str(&format!("\n[trap {}]\n", trap.id));
+        text.push_str(&format!("kind = {:?}\n", trap.kind));
+        text.push_str(&format!("x = {}\ny = {}\n", trap.pos.x, trap.pos.y));
+        text.push_str(&format!("hidden = {}\n", trap.hidden));
+        for id in trap.known_by.iter() {
+            text.push_str(&format!("known_by = {}\n", id));
+        }
+    }
+
+    if let Some(alarm) = game.alarm {
+        text.push_str("\n[alarm]\n");
+        text.push_str(&format!("x = {}\ny = {}\n", alarm.pos.x, alarm.pos.y));
+        text.push_str(&format!("turns = {}\n", alarm.turns));
+    }
+
+    for (i, message) in game.messages.iter().enumerate() {
+        text.push_str(&format!("\n[message {}]\n", i));
+        text.push_str(&format!("text = {}\n", message.text));
+        text.push_str(&format!("kind = {:?}\n", message.kind));
+        text.push_str(&format!("turn = {}\n", message.turn));
+    }
+
+    // written next to the save first, so a crash halfway never leaves a broken save behind
+    let temporary = format!("{}.tmp", path);
+
+    fs::write(&temporary, text)
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|error| format!("could not write {}: {}", path, error))
+}
+
+fn read_entity(section: &Section, id: &str, modes: &[EntityMode]) -> Result<Entity, ContentError> {
+    Ok(Entity {
+        id: read_id(section, id)?,
+        name: section.require("name")?.value.to_owned(),
+        template: section.require("template")?.value.to_owned(),
+        mode: section.require_named("mode", modes)?,
+        relationship: section.require_named("relationship", &RELATIONSHIPS)?,
+        characteristics: EntityCharacteristics {
+            face: section.require("face")?.value.to_owned(),
+            variant: section.require_parsed("variant")?,
+            color: section.require_color("color")?,
+            walkable: section.require_parsed("walkable")?,
+            // only written for locked doors and their keys
+            lock: section.get("lock").map(|entry| entry.value.to_owned()),
+            opens_doors: section.require_parsed("opens_doors")?,
+            swims: section.require_parsed("swims")?,
+            digs: section.require_parsed("digs")?,
+            // only written for walls
+            material: match section.get("material") {
+                Some(_) => Some(section.require_named("material", &MATERIALS)?),
+                None => None,
+            },
+        },
+        stats: EntityStats {
+            health: section.require_int("health")?,
+            max_health: section.require_int("max_health")?,
+            attack: section.require_int("attack")?,
+        },
+        ai: section.require_named("ai", &AIS)?,
+        pos: Position {
+            x: section.require_int("x")?,
+            y: section.require_int("y")?,
+        },
+        busy: section.require_parsed("busy")?,
+        // not part of the save, see core::apply_dialogues
+        dialogue: None,
+    })
+}
+
+fn read_id(section: &Section, id: &str) -> Result<Uuid, ContentError> {
+    Uuid::parse_str(id).map_err(|_| section.error(section.line, format!("invalid id '{}'", id)))
+}
+
+fn read_trap(section: &Section, id: &str) -> Result<Trap, ContentError> {
+    Ok(Trap {
+        id: read_id(section, id)?,
+        kind: section.require_named("kind", &Traps::TRAP_KINDS)?,
+        pos: Position {
+            x: section.require_int("x")?,
+            y: section.require_int("y")?,
+        },
+        hidden: section.require_parsed("hidden")?,
+        known_by: section
+            .get_all("known_by")
+            .iter()
+            .map(|entry| read_id(section, &entry.value))
+            .collect::<Result<Vec<Uuid>, ContentError>>()?,
+    })
+}
+
+// settings come from whoever loads the game, only the map size is taken from the save
+pub fn load_game(path: &str, settings: &Settings) -> Result<Game, ContentError> {
+    let sections = Parser::read_file(path)?;
+
+    let header = sections
+        .iter()
+        .find(|section| section.name == "game")
+        .ok_or_else(|| ContentError {
+            file: path.to_string(),
+            line: 0,
+            entry: None,
+            message: "missing [game]".to_string(),
+        })?;
+
+    let version = header.require_int("version")?;
+    if version != SAVE_VERSION {
+        return Err(header.error(
+            header.require("version")?.line,
+            format!("unsupported save version {}", version),
+        ));
+    }
+
+    let columns = header.require_int("columns")?;
+    let rows = header.require_int("rows")?;
+
+    let mut game = Game {
+        entities: Vec::new(),
+        buildings: Vec::new(),
+        items: Vec::new(),
+        inventory: Vec::new(),
+        traps: Vec::new(),
+        alarm: None,
+        settings: match (settings.graphic.columns, settings.graphic.rows) == (columns, rows) {
+            true => settings.to_owned(),
+            false => StateHelpers::resize_map(settings, rows, columns),
+        },
+        messages: Vec::new(),
+        turn: header.require_parsed("turn")?,
+        terrain_revision: header.require_parsed("terrain_revision")?,
+        events: Vec::new(),
+        vision: Vision {
+            columns,
+            rows,
+            visible: vec![false; (columns * rows).max(0) as usize],
+            explored: Vec::new(),
+        },
+        conversation: None,
+    };
+
+    for section in sections.iter() {
+        match section.kind() {
+            Some(("entity", id)) => game
+                .entities
+                .push(read_entity(section, id, &CREATURE_MODES)?),
+            Some(("building", id)) => {
+                game.buildings
+                    .push(read_entity(section, id, &TERRAIN_MODES)?)
+            }
+            Some(("item", id)) => game.items.push(read_entity(section, id, &ITEM_MODES)?),
+            Some(("carried", id)) => game.inventory.push(read_entity(section, id, &ITEM_MODES)?),
+            Some(("trap", id)) => game.traps.push(read_trap(section, id)?),
+            Some(("message", _)) => game.messages.push(Message {
+                text: section
+                    .get("text")
+                    .map(|entry| entry.value.to_owned())
+                    .unwrap_or_default(),
+                kind: section.require_named("kind", &MESSAGE_KINDS)?,
+                turn: section.require_parsed("turn")?,
+            }),
+            _ if section.name == "game" => (),
+            _ if section.name == "alarm" => {
+                game.alarm = Some(Alarm {
+                    pos: Position {
+                        x: section.require_int("x")?,
+                        y: section.require_int("y")?,
+                    },
+                    turns: section.require_parsed("turns")?,
+                })
+            }
+            _ if section.name == "vision" => {
+                for entry in section.get_all("explored") {
+                    game.vision
+                        .explored
+                        .extend(entry.value.chars().map(|flag| flag == '1'));
+                }
+            }
+            _ => return Err(section.error(section.line, "unknown section".to_string())),
+        }
+    }
+
+    // a hand edited save may not match the map, forget what was explored then
+    if game.vision.explored.len() != game.vision.visible.len() {
+        game.vision.explored = vec![false; game.vision.visible.len()];
+    }
+
+    FieldOfView::update_vision(&mut game);
+
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::state::core as StateCore;
+    use crate::state::test_support as TestSupport;
+    use std::env;
+
+    #[test]
+    fn a_saved_game_loads_back_the_same() {
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+
+        let settings = StateHelpers::get_settings(800, 800, 12, 16, 26);
+        let mut game = StateCore::new_game(settings, &[player]);
+        game.turn = 42;
+        game.entities[0].stats.health = 7;
+        game.inventory.push(StateHelpers::gen_key(
+            "brass",
+            Color::GOLD,
+            game.entities[0].pos,
+        ));
+        game.traps[0].known_by.push(game.entities[0].id);
+
+        let path = env::temp_dir().join(format!("poorguelike-{}.sav", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        save_game(path, &game).unwrap();
+        let loaded = load_game(path, &StateHelpers::get_settings(800, 800, 20, 20, 26));
+        let _ = fs::remove_file(path);
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.turn, 42);
+        assert_eq!(loaded.settings.graphic.rows, 12);
+        assert_eq!(loaded.settings.graphic.columns, 16);
+        assert_eq!(loaded.buildings.len(), game.buildings.len());
+        assert_eq!(loaded.messages[0].text, game.messages[0].text);
+        assert_eq!(loaded.vision.explored, game.vision.explored);
+        assert_eq!(
+            loaded.inventory[0].characteristics.lock,
+            Some("brass".to_string())
+        );
+        assert_eq!(loaded.traps.len(), game.traps.len());
+        assert_eq!(loaded.traps[0].pos, game.traps[0].pos);
+        assert_eq!(loaded.traps[0].known_by, game.traps[0].known_by);
+
+        let player = &loaded.entities[0];
+        assert_eq!(player.id, game.entities[0].id);
+        assert_eq!(player.pos, game.entities[0].pos);
+        assert_eq!(player.mode, EntityMode::Player);
+        assert_eq!(player.stats.health, 7);
+        assert_eq!(
+            player.characteristics.variant,
+            game.entities[0].characteristics.variant
+        );
+    }
+
+    #[test]
+    fn saves_from_another_version_are_refused() {
+        let path = env::temp_dir().join(format!("poorguelike-{}.sav", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        fs::write(
+            path,
+            format!("[game]\nversion = {}\nturn = 3\n", SAVE_VERSION - 1),
+        )
+        .unwrap();
+        let loaded = load_game(path, &StateHelpers::get_settings(800, 800, 12, 16, 26));
+        let _ = fs::remove_file(path);
+        let error = loaded.unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert_eq!(
+            error.message,
+            format!("unsupported save version {}", SAVE_VERSION - 1)
+        );
+    }
+
+    #[test]
+    fn buildings_and_creatures_keep_to_their_own_modes() {
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let settings = StateHelpers::get_settings(800, 800, 12, 16, 26);
+        let game = StateCore::new_game(settings, &[player]);
+
+        let path = env::temp_dir().join(format!("poorguelike-{}.sav", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        save_game(path, &game).unwrap();
+        let text = fs::read_to_string(path).unwrap();
+
+        // a wall turned into a monster, and the player into a wall
+        for (from, to) in [("BrickWall", "Mob"), ("Player", "BrickWall")] {
+            let edited = text.replacen(&format!("mode = {}", from), &format!("mode = {}", to), 1);
+            fs::write(path, edited).unwrap();
+
+            let error = load_game(path, &settings).unwrap_err();
+            assert_eq!(error.message, format!("invalid 'mode': '{}'", to));
+        }
+
+        let _ = fs::remove_file(path);
+    }
+}
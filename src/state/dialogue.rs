@@ -0,0 +1,426 @@
+use crate::state::domain::{
+    Command, Condition, Conversation, DialogueChoice, DialogueLine, Effect, Entity, EntityMode,
+    EntityRelationship, Game, MessageKind, Position,
+};
+use crate::state::helpers as StateHelpers;
+
+// Talking to friendly creatures: walking into one with something to say opens its dialogue
+// (see content/dialogues.cfg) and from then on the number keys pick answers, until one of
+// them ends it or the player walks away with escape. Talking takes no time, nothing else
+// moves until the conversation is over.
+
+// the items a dialogue can hand out
+pub const GIFTS: [&str; 1] = ["pickaxe"];
+
+// longest line of text in the conversation panel
+const WRAP_WIDTH: usize = 48;
+
+fn gen_gift(name: &str, pos: Position) -> Option<Entity> {
+    match name {
+        "pickaxe" => Some(StateHelpers::gen_pickaxe(pos)),
+        _ => None,
+    }
+}
+
+// index of the creature the command walks the player into, when it has something to say
+pub fn get_listener(game: &Game, command: Command) -> Option<usize> {
+    let side = StateHelpers::get_command_side(command)?;
+    let player = StateHelpers::get_player(game)?;
+    let target = StateHelpers::get_position_towards(&player.pos, side);
+
+    game.entities.iter().position(|entity| {
+        entity.pos == target
+            && entity.stats.health > 0
+            && entity.dialogue.is_some()
+            && matches!(entity.relationship, EntityRelationship::Friendly)
+    })
+}
+
+fn get_speaker(game: &Game) -> Option<&Entity> {
+    let conversation = game.conversation.as_ref()?;
+
+    game.entities
+        .iter()
+        .find(|entity| entity.id == conversation.with)
+}
+
+// what the creature being talked to said last
+fn get_line(game: &Game) -> Option<&DialogueLine> {
+    let conversation = game.conversation.as_ref()?;
+
+    get_speaker(game)?
+        .dialogue
+        .as_ref()?
+        .lines
+        .iter()
+        .find(|line| line.id == conversation.line)
+}
+
+fn holds(condition: &Condition, player: &Entity, game: &Game) -> bool {
+    let carries = |name: &str| game.inventory.iter().any(|item| item.name == name);
+
+    match condition {
+        Condition::Has(name) => carries(name),
+        Condition::Lacks(name) => !carries(name),
+        Condition::Hurt => player.stats.health < player.stats.max_health,
+        Condition::Unhurt => player.stats.health >= player.stats.max_health,
+    }
+}
+
+// the answers the player can give right now, numbered from 1 in this order
+pub fn get_choices(game: &Game) -> Vec<&DialogueChoice> {
+    let (line, player) = match (get_line(game), StateHelpers::get_player(game)) {
+        (Some(line), Some(player)) => (line, player),
+        _ => return Vec::new(),
+    };
+
+    line.choices
+        .iter()
+        .filter(|choice| {
+            choice
+                .conditions
+                .iter()
+                .all(|condition| holds(condition, player, game))
+        })
+        .collect()
+}
+
+// moves the conversation on to that line, or ends it without one
+fn go_to(game: &mut Game, line: Option<String>) {
+    let with = match (&game.conversation, line) {
+        (Some(conversation), Some(line)) => Conversation {
+            with: conversation.with,
+            line,
+        },
+        _ => {
+            game.conversation = None;
+            return;
+        }
+    };
+
+    game.conversation = Some(with);
+
+    // whatever gets said ends up in the log too, so it can be read again later
+    if let (Some(speaker), Some(line)) = (get_speaker(game), get_line(game)) {
+        let text = format!("The {} says: \"{}\"", speaker.name, line.text);
+        game.messages.push(StateHelpers::gen_message(
+            &text,
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+}
+
+pub fn start(game: &mut Game, listener: usize) {
+    let entity = &game.entities[listener];
+    let first = match entity
+        .dialogue
+        .as_ref()
+        .and_then(|dialogue| dialogue.lines.first())
+    {
+        Some(first) => first.id.to_owned(),
+        None => return,
+    };
+
+    game.conversation = Some(Conversation {
+        with: entity.id,
+        line: String::new(),
+    });
+
+    go_to(game, Some(first));
+}
+
+fn apply(effect: &Effect, game: &mut Game) {
+    let speaker = match game.conversation.as_ref().and_then(|conversation| {
+        game.entities
+            .iter()
+            .position(|entity| entity.id == conversation.with)
+    }) {
+        Some(speaker) => speaker,
+        None => return,
+    };
+    let name = game.entities[speaker].name.to_owned();
+
+    let (text, kind) = match effect {
+        Effect::Give(item) => {
+            let gift = match gen_gift(item, game.entities[speaker].pos) {
+                Some(gift) => gift,
+                None => return,
+            };
+
+            game.inventory.push(gift);
+            (
+                format!("The {} gives you a {}.", name, item),
+                MessageKind::Info,
+            )
+        }
+        Effect::Take(item) => {
+            match game
+                .inventory
+                .iter()
+                .position(|carried| carried.name == *item)
+            {
+                Some(index) => game.inventory.remove(index),
+                None => return,
+            };
+
+            (
+                format!("You hand the {} over to the {}.", item, name),
+                MessageKind::Info,
+            )
+        }
+        Effect::Heal(amount) => {
+            let player = match game
+                .entities
+                .iter_mut()
+                .find(|entity| entity.mode == EntityMode::Player)
+            {
+                Some(player) => player,
+                None => return,
+            };
+
+            player.stats.health = (player.stats.health + amount).min(player.stats.max_health);
+            (format!("The {} patches you up.", name), MessageKind::Info)
+        }
+        Effect::Become(relationship) => {
+            let entity = &mut game.entities[speaker];
+            entity.relationship = *relationship;
+            entity.mode = match relationship {
+                EntityRelationship::Foe => EntityMode::Mob,
+                _ => EntityMode::NPC,
+            };
+
+            match relationship {
+                EntityRelationship::Foe => {
+                    (format!("The {} turns on you!", name), MessageKind::Warning)
+                }
+                EntityRelationship::Neutral => (
+                    format!("The {} loses interest in you.", name),
+                    MessageKind::Info,
+                ),
+                EntityRelationship::Friendly => {
+                    (format!("The {} warms up to you.", name), MessageKind::Info)
+                }
+                EntityRelationship::None => return,
+            }
+        }
+    };
+
+    game.messages
+        .push(StateHelpers::gen_message(&text, kind, game.turn));
+}
+
+// a number picks that answer, escape (or enter once there is nothing left to answer) leaves
+pub fn answer(game: &mut Game, command: Command) {
+    let choices = get_choices(game);
+
+    match command {
+        Command::Cancel => game.conversation = None,
+        Command::Confirm if choices.is_empty() => game.conversation = None,
+        Command::Count(number) if (1..=choices.len()).contains(&(number as usize)) => {
+            let choice = choices[number as usize - 1].to_owned();
+
+            for effect in choice.effects.iter() {
+                apply(effect, game);
+            }
+
+            // only friendly creatures talk, one that stopped being friendly has nothing more
+            // to say whatever the choice leads to
+            let next = match get_speaker(game).map(|speaker| speaker.relationship) {
+                Some(EntityRelationship::Friendly) => choice.next,
+                _ => None,
+            };
+
+            go_to(game, next);
+        }
+        _ => (),
+    }
+}
+
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > WRAP_WIDTH {
+            lines.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    lines.push(current);
+    lines
+}
+
+// what the conversation panel shows, empty when nobody is talking
+pub fn describe(game: &Game) -> Vec<String> {
+    let (speaker, line) = match (get_speaker(game), get_line(game)) {
+        (Some(speaker), Some(line)) => (speaker, line),
+        _ => return Vec::new(),
+    };
+
+    let mut lines = vec![StateHelpers::capitalize(&speaker.name)];
+    lines.extend(wrap(&line.text));
+    lines.push(String::new());
+
+    let choices = get_choices(game);
+
+    for (number, choice) in choices.iter().enumerate() {
+        lines.push(format!("[{}] {}", number + 1, choice.text));
+    }
+
+    lines.push(match choices.is_empty() {
+        true => "[Enter] leave".to_string(),
+        false => "[Esc] leave".to_string(),
+    });
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::dialogues as Dialogues;
+    use crate::content::parser as Parser;
+    use crate::state::core as StateCore;
+    use crate::state::domain::{EntityAi, GameEvent};
+    use crate::state::test_support as TestSupport;
+
+    const DIALOGUES: &str = "
+[dialogue hermit]
+start = hello
+
+[line hello]
+text = Hm?
+choice = ask_help
+choice = ask_tool
+choice = insult
+
+[choice ask_help]
+text = Help me.
+if = hurt
+do = heal 5
+do = become friendly
+
+[choice ask_tool]
+text = Got a pickaxe?
+if = lacks pickaxe
+do = give pickaxe
+next = given
+
+[line given]
+text = Now go away.
+
+[choice insult]
+text = Move.
+do = become foe
+next = given
+";
+
+    // a 5x3 corridor, the player at one end with the hermit standing right next to it
+    fn get_corridor_game() -> Game {
+        let settings = StateHelpers::get_settings(120, 200, 3, 5, 26);
+        let buildings = TestSupport::tiles(&settings, |x, y| match (x, y) {
+            (1..=3, 1) => EntityMode::Ground,
+            _ => EntityMode::BrickWall,
+        });
+
+        let sections = Parser::parse("dialogues.cfg", DIALOGUES).unwrap();
+        let mut hermit =
+            TestSupport::template("hermit", EntityAi::Wander, EntityRelationship::Friendly);
+        hermit.dialogue = Dialogues::parse_dialogues(&sections).unwrap().pop();
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+
+        TestSupport::game(
+            settings,
+            buildings,
+            vec![
+                StateHelpers::gen_creature(&player, Position { x: 1, y: 1 }),
+                StateHelpers::gen_creature(&hermit, Position { x: 2, y: 1 }),
+            ],
+        )
+    }
+
+    #[test]
+    fn walking_into_a_friendly_creature_talks_without_taking_turns() {
+        let mut game = get_corridor_game();
+        let settings = game.settings;
+        let hermit = game.entities[1].id;
+
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(
+            game.conversation,
+            Some(Conversation {
+                with: hermit,
+                line: "hello".to_string(),
+            })
+        );
+        assert_eq!(game.turn, 0);
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "The hermit says: \"Hm?\""
+        );
+
+        // unhurt, so there's no asking for help
+        let choices: Vec<&str> = get_choices(&game)
+            .iter()
+            .map(|choice| choice.text.as_str())
+            .collect();
+        assert_eq!(choices, vec!["Got a pickaxe?", "Move."]);
+
+        // moving around does nothing while talking
+        game = StateCore::update_game_states(&game, &settings, Command::MoveLeft);
+        assert_eq!(game.entities[0].pos, Position { x: 1, y: 1 });
+
+        game = StateCore::update_game_states(&game, &settings, Command::Count(1));
+        assert_eq!(game.inventory[0].name, "pickaxe");
+        assert_eq!(game.conversation.as_ref().unwrap().line, "given");
+        assert_eq!(describe(&game).last().unwrap(), "[Enter] leave");
+
+        game = StateCore::update_game_states(&game, &settings, Command::Confirm);
+        assert_eq!(game.conversation, None);
+        assert_eq!(game.turn, 0);
+
+        // with the pickaxe carried and some health gone the choices change
+        game.entities[0].stats.health = 2;
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(get_choices(&game)[0].text, "Help me.");
+        assert_eq!(get_choices(&game).len(), 2);
+
+        game = StateCore::update_game_states(&game, &settings, Command::Count(1));
+        assert_eq!(game.entities[0].stats.health, 7);
+        assert_eq!(game.conversation, None);
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "The hermit warms up to you."
+        );
+    }
+
+    #[test]
+    fn a_creature_turned_hostile_stops_talking_and_fights() {
+        let mut game = get_corridor_game();
+        let settings = game.settings;
+
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        // the insult leads on to another line, but a foe has nothing more to say
+        game = StateCore::update_game_states(&game, &settings, Command::Count(2));
+        assert_eq!(game.conversation, None);
+        assert_eq!(game.entities[1].mode, EntityMode::Mob);
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "The hermit turns on you!"
+        );
+
+        // the next bump is an attack, and a turn
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(game.turn, 1);
+        assert!(game
+            .events
+            .iter()
+            .any(|event| matches!(event, GameEvent::Attacked { .. })));
+    }
+}
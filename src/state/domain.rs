@@ -1,5 +1,4 @@
-use crate::draw::TileNames;
-use raylib::color::Color;
+use crate::color::Color;
 use uuid::Uuid;
 
 #[derive(Debug, Clone, Copy)]
@@ -10,28 +9,65 @@ pub enum EntityRelationship {
     None,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EntityMode {
     BrickWall,
     Ground,
+    // walkable while open
+    Door,
+    // slow to walk through, see state::terrain
+    ShallowWater,
+    Rubble,
+    // only swimmers get in
+    DeepWater,
+    // burns whoever steps in
+    Lava,
     Player,
     NPC,
     Mob,
+    Item,
+}
+
+// what walls are made of, see state::terrain for how long each takes to dig through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Material {
+    Brick,
+    // the edge of the map, nothing gets through it
+    Bedrock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityAi {
+    None,
+    Player,
+    Wander,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EntityStats {
+    pub health: i32,
+    pub max_health: i32,
+    pub attack: i32,
 }
 
 #[derive(Debug, Clone)]
 pub struct EntityCharacteristics {
-    pub face: TileNames,
+    pub face: String,
+    pub variant: u32,
     pub color: Color,
     pub walkable: bool,
+    // the key a door needs, or the door a key opens
+    pub lock: Option<String>,
+    pub opens_doors: bool,
+    pub swims: bool,
+    // tunnels through walls, or for items, can be used to
+    pub digs: bool,
+    // walls only
+    pub material: Option<Material>,
 }
 
-pub struct TilePosition {
-    pub x: f32,
-    pub y: f32,
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -40,12 +76,102 @@ pub struct Position {
 #[derive(Debug, Clone)]
 pub struct Entity {
     pub id: Uuid,
+    pub name: String,
+    pub template: String,
     pub mode: EntityMode,
     pub relationship: EntityRelationship,
     pub characteristics: EntityCharacteristics,
+    pub stats: EntityStats,
+    pub ai: EntityAi,
     pub pos: Position,
+    // turns left before it can act again, ex: after wading into water
+    pub busy: u32,
+    // what it says when the player walks into it, from its creature template
+    pub dialogue: Option<Dialogue>,
+}
+
+// has to hold for a dialogue choice to be offered, always about the player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    // carrying an item with that name
+    Has(String),
+    Lacks(String),
+    Hurt,
+    Unhurt,
+}
+
+// what picking a dialogue choice does
+#[derive(Debug, Clone)]
+pub enum Effect {
+    // an item for the player, see dialogue::GIFTS
+    Give(String),
+    // the first item with that name out of the inventory
+    Take(String),
+    Heal(i32),
+    // the creature being talked to changes its mind about the player
+    Become(EntityRelationship),
 }
 
+#[derive(Debug, Clone)]
+pub struct DialogueChoice {
+    pub text: String,
+    pub conditions: Vec<Condition>,
+    pub effects: Vec<Effect>,
+    // the conversation ends after this choice without one
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DialogueLine {
+    pub id: String,
+    pub text: String,
+    pub choices: Vec<DialogueChoice>,
+}
+
+// a conversation tree from content/dialogues.cfg, the first line is where it starts
+#[derive(Debug, Clone)]
+pub struct Dialogue {
+    pub id: String,
+    pub lines: Vec<DialogueLine>,
+}
+
+// who the player is talking to and what was said last, see state::dialogue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversation {
+    pub with: Uuid,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapKind {
+    Spike,
+    // sends whoever steps on it somewhere else on the map
+    Teleport,
+    // calls every monster over
+    Alarm,
+    // whoever falls in has to climb back out
+    Pit,
+}
+
+// goes off every time something steps on it, hidden until the player finds it
+#[derive(Debug, Clone)]
+pub struct Trap {
+    pub id: Uuid,
+    pub kind: TrapKind,
+    pub pos: Position,
+    pub hidden: bool,
+    // the creatures that know it is there and walk around it
+    pub known_by: Vec<Uuid>,
+}
+
+// where an alarm went off, monsters keep heading there for a few more turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alarm {
+    pub pos: Position,
+    pub turns: u32,
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub struct GraphicSettings {
     pub tile_height: i32,
@@ -63,15 +189,188 @@ pub struct WindowSettings {
     pub width: i32,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct HudSettings {
+    pub log_lines: i32,
+    pub log_font_size: i32,
+    pub log_height: i32,
+}
+
+// how the map gets laid out, see mapgen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    // a single room as big as the map
+    Arena,
+    Rooms,
+}
+
+// what a new game is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldSettings {
+    pub generator: Generator,
+    pub npcs: i32,
+    pub mobs: i32,
+}
+
+// which keys move around, see core::get_command_for_key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPreset {
+    Vi,
+    Wasd,
+    Arrows,
+}
+
+// colours for everything that isn't a sprite, see render::palette_colors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Palette {
+    Default,
+    HighContrast,
+    Colorblind,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Settings {
     pub window: WindowSettings,
     pub graphic: GraphicSettings,
+    pub hud: HudSettings,
+    pub debug: bool,
+    pub animations: bool,
+    pub keys: KeyPreset,
+    pub palette: Palette,
+    pub world: WorldSettings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Wait,
+    ScrollLogUp,
+    ScrollLogDown,
+    ToggleDebug,
+    ToggleAnimations,
+    ToggleMinimap,
+    Look,
+    Explore,
+    Rest,
+    // opens the doors around the player, or closes them
+    Door,
+    PickUp,
+    // looks around for hidden traps
+    Search,
+    // a digit of the count typed before a command
+    Count(u8),
+    Confirm,
+    Cancel,
+}
+
+// a key the game has a use for, whichever frontend read it (see core::get_command_for_key)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    // always upper case, shift is read on its own
+    Letter(char),
+    Digit(u8),
+    Left,
+    Down,
+    Up,
+    Right,
+    Period,
+    PageUp,
+    PageDown,
+    F3,
+    F4,
+    Enter,
+    Escape,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Info,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub text: String,
+    pub kind: MessageKind,
+    pub turn: u32,
+}
+
+// what happened during the last turn, so frontends can show it instead of just the outcome
+#[derive(Debug, Clone)]
+pub enum GameEvent {
+    Moved {
+        id: Uuid,
+        from: Position,
+        to: Position,
+    },
+    // taken somewhere else at once (by a teleport trap) right after moving
+    Teleported {
+        id: Uuid,
+        from: Position,
+        to: Position,
+    },
+    Bumped {
+        id: Uuid,
+        from: Position,
+        towards: Position,
+        target: Option<Uuid>,
+    },
+    Attacked {
+        attacker: Uuid,
+        target: Uuid,
+        pos: Position,
+        damage: i32,
+    },
+    // hurt by the map itself, ex: lava or a trap
+    Hurt {
+        id: Uuid,
+        pos: Position,
+        damage: i32,
+    },
+    // the entity as it was when it died, it is gone from the game by now
+    Died {
+        entity: Entity,
+    },
+}
+
+// what the player sees right now and what it has seen so far, one flag per tile row by row
+#[derive(Debug, Clone, Default)]
+pub struct Vision {
+    pub columns: i32,
+    pub rows: i32,
+    pub visible: Vec<bool>,
+    pub explored: Vec<bool>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Hud {
+    pub log_scroll: usize,
+    pub look: Option<Position>,
+    pub last_mouse_tile: Option<Position>,
+    pub minimap: bool,
+    // how many times the next command should be done, 0 when no count was typed
+    pub count: u32,
 }
 
 #[derive(Debug, Clone)]
 pub struct Game {
     pub entities: Vec<Entity>,
     pub buildings: Vec<Entity>,
+    // lying on the ground, and carried by the player
+    pub items: Vec<Entity>,
+    pub inventory: Vec<Entity>,
+    pub traps: Vec<Trap>,
+    pub alarm: Option<Alarm>,
     pub settings: Settings,
+    pub messages: Vec<Message>,
+    pub turn: u32,
+    // bumped whenever buildings change, so the cached terrain layer knows to redraw
+    pub terrain_revision: u32,
+    pub events: Vec<GameEvent>,
+    pub vision: Vision,
+    // while talking the world waits, every key goes to the conversation
+    pub conversation: Option<Conversation>,
 }
@@ -0,0 +1,203 @@
+use crate::state::domain::{Game, Position, Vision};
+use crate::state::helpers as StateHelpers;
+use uuid::Uuid;
+
+// how far the player sees, in tiles
+pub const VISION_RADIUS: i32 = 8;
+
+impl Vision {
+    fn index(&self, pos: &Position) -> Option<usize> {
+        match pos.x >= 0 && pos.y >= 0 && pos.x < self.columns && pos.y < self.rows {
+            true => Some((pos.y * self.columns + pos.x) as usize),
+            false => None,
+        }
+    }
+
+    pub fn is_visible(&self, pos: &Position) -> bool {
+        self.index(pos)
+            .and_then(|index| self.visible.get(index).copied())
+            .unwrap_or(false)
+    }
+
+    pub fn is_explored(&self, pos: &Position) -> bool {
+        self.index(pos)
+            .and_then(|index| self.explored.get(index).copied())
+            .unwrap_or(false)
+    }
+}
+
+// every tile along a straight line, both ends included
+fn line(from: &Position, to: &Position) -> Vec<Position> {
+    let mut points: Vec<Position> = Vec::new();
+
+    let dx = (to.x - from.x).abs();
+    let dy = -(to.y - from.y).abs();
+    let step_x = if from.x < to.x { 1 } else { -1 };
+    let step_y = if from.y < to.y { 1 } else { -1 };
+
+    let mut error = dx + dy;
+    let mut current = *from;
+
+    loop {
+        points.push(current);
+
+        if current == *to {
+            return points;
+        }
+
+        let doubled = 2 * error;
+
+        if doubled >= dy {
+            error += dy;
+            current.x += step_x;
+        }
+
+        if doubled <= dx {
+            error += dx;
+            current.y += step_y;
+        }
+    }
+}
+
+// the tiles that block the view, indexed like Vision
+fn opaque_tiles(game: &Game, vision: &Vision) -> Vec<bool> {
+    let mut opaque = vec![false; (vision.columns * vision.rows).max(0) as usize];
+
+    for building in game.buildings.iter() {
+        if let Some(index) = vision.index(&building.pos) {
+            opaque[index] = !building.characteristics.walkable;
+        }
+    }
+
+    opaque
+}
+
+// close enough, and only what stands between the two ends can block the view
+fn in_sight(vision: &Vision, opaque: &[bool], from: &Position, to: &Position) -> bool {
+    let distance = (to.x - from.x).pow(2) + (to.y - from.y).pow(2);
+    if distance > VISION_RADIUS.pow(2) || vision.index(to).is_none() {
+        return false;
+    }
+
+    let path = line(from, to);
+
+    !path
+        .iter()
+        .skip(1)
+        .take(path.len().saturating_sub(2))
+        .any(|pos| vision.index(pos).map(|index| opaque[index]).unwrap_or(true))
+}
+
+// every creature that can see the tile from where it stands, the player included
+pub fn get_witnesses(game: &Game, pos: &Position) -> Vec<Uuid> {
+    let columns = game.settings.graphic.columns;
+    let rows = game.settings.graphic.rows;
+    let map = Vision {
+        columns,
+        rows,
+        ..Vision::default()
+    };
+    let opaque = opaque_tiles(game, &map);
+
+    game.entities
+        .iter()
+        .filter(|entity| in_sight(&map, &opaque, &entity.pos, pos))
+        .map(|entity| entity.id)
+        .collect()
+}
+
+// recomputes what the player sees from where it stands, and remembers it as explored, walls
+// block the view but are seen themselves
+pub fn update_vision(game: &mut Game) {
+    let columns = game.settings.graphic.columns;
+    let rows = game.settings.graphic.rows;
+    let size = (columns * rows).max(0) as usize;
+
+    if game.vision.columns != columns || game.vision.rows != rows {
+        game.vision = Vision {
+            columns,
+            rows,
+            visible: vec![false; size],
+            explored: vec![false; size],
+        };
+    }
+
+    let opaque = opaque_tiles(game, &game.vision);
+    let mut visible = vec![false; size];
+
+    if let Some(origin) = StateHelpers::get_player(game).map(|player| player.pos) {
+        for y in (origin.y - VISION_RADIUS)..=(origin.y + VISION_RADIUS) {
+            for x in (origin.x - VISION_RADIUS)..=(origin.x + VISION_RADIUS) {
+                let target = Position { x, y };
+
+                if in_sight(&game.vision, &opaque, &origin, &target) {
+                    if let Some(index) = game.vision.index(&target) {
+                        visible[index] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    for (index, seen) in visible.iter().enumerate() {
+        if *seen {
+            game.vision.explored[index] = true;
+        }
+    }
+
+    game.vision.visible = visible;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::domain::{EntityAi, EntityMode, EntityRelationship};
+    use crate::state::test_support as TestSupport;
+
+    #[test]
+    fn walls_block_the_view_and_what_was_seen_stays_explored() {
+        // a 20x5 room cut in two by a wall at x = 5
+        let settings = StateHelpers::get_settings(100, 400, 5, 20, 20);
+        let buildings = TestSupport::tiles(&settings, |x, y| match (x, y) {
+            (0 | 5 | 19, _) | (_, 0 | 4) => EntityMode::BrickWall,
+            _ => EntityMode::Ground,
+        });
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let ghost = TestSupport::template("ghost", EntityAi::Wander, EntityRelationship::Foe);
+        let mut game = TestSupport::game(
+            settings,
+            buildings,
+            vec![
+                StateHelpers::gen_creature(&player, Position { x: 2, y: 2 }),
+                StateHelpers::gen_creature(&ghost, Position { x: 10, y: 2 }),
+            ],
+        );
+
+        assert!(game.vision.is_visible(&Position { x: 4, y: 2 }));
+        assert!(game.vision.is_visible(&Position { x: 5, y: 2 }));
+        assert!(!game.vision.is_visible(&Position { x: 6, y: 2 }));
+        assert!(!game.vision.is_explored(&Position { x: 6, y: 2 }));
+        assert!(!game.vision.is_visible(&Position { x: -1, y: 2 }));
+
+        let player_id = game.entities[0].id;
+        let ghost_id = game.entities[1].id;
+        assert_eq!(
+            get_witnesses(&game, &Position { x: 4, y: 2 }),
+            vec![player_id]
+        );
+        assert_eq!(
+            get_witnesses(&game, &Position { x: 8, y: 3 }),
+            vec![ghost_id]
+        );
+
+        // past the wall, the first half is remembered but out of sight
+        game.entities[0].pos = Position { x: 7, y: 2 };
+        update_vision(&mut game);
+
+        assert!(game.vision.is_visible(&Position { x: 6, y: 2 }));
+        assert!(game.vision.is_visible(&Position { x: 15, y: 2 }));
+        assert!(!game.vision.is_visible(&Position { x: 16, y: 2 }));
+        assert!(!game.vision.is_visible(&Position { x: 2, y: 2 }));
+        assert!(game.vision.is_explored(&Position { x: 2, y: 2 }));
+    }
+}
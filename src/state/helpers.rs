@@ -1,13 +1,17 @@
 use crate::state::domain::{
-    Entity, EntityCharacteristics, EntityMode, EntityRelationship, Game, GraphicSettings, Position,
-    Settings, WindowSettings,
+    Command, Entity, EntityAi, EntityCharacteristics, EntityMode, EntityRelationship, EntityStats,
+    Game, Generator, GraphicSettings, HudSettings, KeyPreset, Material, Message, MessageKind,
+    Palette, Position, Settings, WindowSettings, WorldSettings,
 };
 
-use crate::draw::TileNames;
+use crate::color::Color;
+use crate::content::creatures::CreatureTemplate;
+use crate::diagnostics as Diagnostics;
+use crate::diagnostics::{Level, TARGET_AI};
+use crate::state::random as Random;
+use crate::state::terrain as Terrain;
+use crate::state::traps as Traps;
 use rand::Rng;
-use raylib::color::Color;
-use raylib::drawing::RaylibDrawHandle;
-use raylib::prelude::KeyboardKey;
 use uuid::Uuid;
 
 pub fn get_entities_in_tile(game: &Game, tile_pos: &Position) -> Vec<Entity> {
@@ -19,6 +23,12 @@ pub fn get_entities_in_tile(game: &Game, tile_pos: &Position) -> Vec<Entity> {
         }
     }
 
+    for item in &game.items {
+        if item.pos == *tile_pos {
+            to_return.push(item.to_owned());
+        }
+    }
+
     for entity in &game.entities {
         if entity.pos.x == tile_pos.x && entity.pos.y == tile_pos.y {
             to_return.push(entity.to_owned());
@@ -28,139 +38,669 @@ pub fn get_entities_in_tile(game: &Game, tile_pos: &Position) -> Vec<Entity> {
     to_return
 }
 
-pub fn gen_random_position(entities: &Vec<Entity>, max_cols: i32, max_rows: i32) -> Position {
-    let mut rng = rand::thread_rng();
+pub fn get_player(game: &Game) -> Option<&Entity> {
+    game.entities
+        .iter()
+        .find(|entity| matches!(entity.mode, EntityMode::Player))
+}
 
-    let mut found = false;
-    let mut final_position = Position { x: 0, y: 0 };
+// a walkable tile nobody stands on and that hurts nobody, None once the map is full
+pub fn gen_random_position(entities: &[Entity], buildings: &[Entity]) -> Option<Position> {
+    gen_random_position_except(entities, buildings, &[])
+}
 
-    while found == false {
-        let random_x: i32 = rng.gen_range(1..max_cols);
-        let random_y: i32 = rng.gen_range(1..max_rows);
+// same as gen_random_position, never picking any of the except tiles
+pub fn gen_random_position_except(
+    entities: &[Entity],
+    buildings: &[Entity],
+    except: &[Position],
+) -> Option<Position> {
+    let candidates: Vec<Position> = buildings
+        .iter()
+        .filter(|building| building.characteristics.walkable && !Terrain::is_hazard(building.mode))
+        .map(|building| building.pos)
+        .filter(|pos| position_free(pos, entities) && !except.contains(pos))
+        .collect();
+
+    if candidates.is_empty() {
+        return None;
+    }
 
-        let pos = Position {
-            x: random_x,
-            y: random_y,
-        };
+    Some(Random::with_rng(|rng| {
+        candidates[rng.gen_range(0..candidates.len())]
+    }))
+}
 
-        if position_free(&pos, entities) {
-            final_position = pos;
-            found = true;
-        }
+// terrain is the same everywhere, creatures come from content/creatures.cfg (see gen_creature)
+pub fn gen_terrain(terrain_mode: EntityMode, position: Position) -> Entity {
+    // the sprite variant is rolled here but picked by the tileset weights when drawing
+    let (face, terrain_name) = match terrain_mode {
+        EntityMode::BrickWall => ("brick_wall", "brick wall"),
+        EntityMode::Ground => match Random::with_rng(|rng| rng.gen_range(1..=100)) {
+            1..=66 => ("dirt", "dirt"),
+            _ => ("grass", "grass"),
+        },
+        EntityMode::Door => ("door_closed", "closed door"),
+        EntityMode::ShallowWater => ("water_shallow", "shallow water"),
+        EntityMode::Rubble => ("rubble", "rubble"),
+        EntityMode::DeepWater => ("water_deep", "deep water"),
+        EntityMode::Lava => ("lava", "lava"),
+        _ => unreachable!("{:?} is not terrain", terrain_mode),
+    };
+
+    // walls are brick unless the map says otherwise, their health is what digging wears down
+    let material = match terrain_mode {
+        EntityMode::BrickWall => Some(Material::Brick),
+        _ => None,
+    };
+    let health = material.map(Terrain::get_wall_health).unwrap_or(0);
+
+    Entity {
+        id: Uuid::new_v4(),
+        name: terrain_name.to_string(),
+        template: String::new(),
+        mode: terrain_mode,
+        relationship: EntityRelationship::None,
+        characteristics: EntityCharacteristics {
+            face: face.to_string(),
+            variant: Random::with_rng(|rng| rng.gen()),
+            color: Color::from_hex("333333").unwrap(),
+            walkable: !matches!(terrain_mode, EntityMode::BrickWall | EntityMode::Door),
+            lock: None,
+            opens_doors: false,
+            swims: false,
+            digs: false,
+            material,
+        },
+        stats: EntityStats {
+            health,
+            max_health: health,
+            attack: 0,
+        },
+        ai: EntityAi::None,
+        pos: position,
+        busy: 0,
+        dialogue: None,
     }
+}
 
-    final_position
+// part of the edge of the map, can't be dug through
+pub fn make_bedrock(wall: &mut Entity) {
+    wall.characteristics.material = Some(Material::Bedrock);
+    wall.stats.health = 0;
+    wall.stats.max_health = 0;
 }
 
-fn get_random_grass() -> TileNames {
-    let mut rng = rand::thread_rng();
-    let random_grass: u8 = rng.gen_range(0..=100);
-    let grass_texture: &TileNames = match random_grass {
-        0..=50 => &TileNames::Grass1,
-        51..=75 => &TileNames::Grass2,
-        76..=100 => &TileNames::Grass3,
-        _ => unreachable!(),
+pub fn gen_creature(template: &CreatureTemplate, position: Position) -> Entity {
+    Entity {
+        id: Uuid::new_v4(),
+        name: template.name.to_owned(),
+        template: template.id.to_owned(),
+        mode: template.mode(),
+        relationship: template.relationship,
+        characteristics: EntityCharacteristics {
+            face: template.face.to_owned(),
+            variant: Random::with_rng(|rng| rng.gen()),
+            color: template.color,
+            walkable: false,
+            lock: None,
+            opens_doors: template.opens_doors,
+            swims: template.swims,
+            digs: template.digs,
+            material: None,
+        },
+        stats: EntityStats {
+            health: template.health,
+            max_health: template.health,
+            attack: template.attack,
+        },
+        ai: template.ai,
+        pos: position,
+        busy: 0,
+        dialogue: template.dialogue.clone(),
+    }
+}
+
+// a key for the doors locked with lock, tinted with color
+pub fn gen_key(lock: &str, color: Color, position: Position) -> Entity {
+    Entity {
+        id: Uuid::new_v4(),
+        name: format!("{} key", lock),
+        template: String::new(),
+        mode: EntityMode::Item,
+        relationship: EntityRelationship::None,
+        characteristics: EntityCharacteristics {
+            face: "key".to_string(),
+            variant: Random::with_rng(|rng| rng.gen()),
+            color,
+            walkable: true,
+            lock: Some(lock.to_string()),
+            opens_doors: false,
+            swims: false,
+            digs: false,
+            material: None,
+        },
+        stats: EntityStats {
+            health: 0,
+            max_health: 0,
+            attack: 0,
+        },
+        ai: EntityAi::None,
+        pos: position,
+        busy: 0,
+        dialogue: None,
+    }
+}
+
+// digs through walls for whoever carries it, its attack is how hard each blow hits
+pub fn gen_pickaxe(position: Position) -> Entity {
+    Entity {
+        id: Uuid::new_v4(),
+        name: "pickaxe".to_string(),
+        template: String::new(),
+        mode: EntityMode::Item,
+        relationship: EntityRelationship::None,
+        characteristics: EntityCharacteristics {
+            face: "pickaxe".to_string(),
+            variant: Random::with_rng(|rng| rng.gen()),
+            color: Color::from_hex("B0B0B0").unwrap(),
+            walkable: true,
+            lock: None,
+            opens_doors: false,
+            swims: false,
+            digs: true,
+            material: None,
+        },
+        stats: EntityStats {
+            health: 0,
+            max_health: 0,
+            attack: 5,
+        },
+        ai: EntityAi::None,
+        pos: position,
+        busy: 0,
+        dialogue: None,
+    }
+}
+
+pub fn lock_door(door: &mut Entity, lock: &str) {
+    door.name = "locked door".to_string();
+    door.characteristics.face = "door_locked".to_string();
+    door.characteristics.walkable = false;
+    door.characteristics.lock = Some(lock.to_string());
+}
+
+// an open door has no lock left, the key stays with whoever used it
+pub fn set_door_open(door: &mut Entity, open: bool) {
+    let (face, name) = match open {
+        true => ("door_open", "open door"),
+        false => ("door_closed", "closed door"),
     };
 
-    grass_texture.to_owned()
+    door.name = name.to_string();
+    door.characteristics.face = face.to_string();
+    door.characteristics.walkable = open;
+    door.characteristics.lock = None;
 }
 
-fn get_random_dirt() -> TileNames {
-    let mut rng = rand::thread_rng();
-    let random_grass: u8 = rng.gen_range(0..=100);
-    let grass_texture: &TileNames = match random_grass {
-        0..=50 => &TileNames::Dirt0,
-        51..=60 => &TileNames::Dirt1,
-        61..=80 => &TileNames::Dirt2,
-        81..=90 => &TileNames::Dirt3,
-        91..=100 => &TileNames::Dirt4,
-        _ => unreachable!(),
+// can be walked on, or will be once bumped into
+pub fn is_passable(building: &Entity) -> bool {
+    building.characteristics.walkable
+        || (building.mode == EntityMode::Door && building.characteristics.lock.is_none())
+}
+
+// index of the closed door that moving towards side would bump into
+pub fn get_closed_door(entity: &Entity, side: &str, game: &Game) -> Option<usize> {
+    let target = get_position_towards(&entity.pos, side);
+
+    game.buildings.iter().position(|building| {
+        building.pos == target
+            && building.mode == EntityMode::Door
+            && !building.characteristics.walkable
+    })
+}
+
+// the player can open any door it has the key for, other creatures only unlocked ones and
+// only if their template says so, returns whether the door opened
+pub fn open_door(
+    entity: &Entity,
+    game: &mut Game,
+    door_index: usize,
+    messages: &mut Vec<Message>,
+) -> bool {
+    let is_player = entity.mode == EntityMode::Player;
+    let door = &game.buildings[door_index];
+
+    let text = match (&door.characteristics.lock, is_player) {
+        (None, true) => "You open the door.".to_string(),
+        (None, false) if entity.characteristics.opens_doors => {
+            format!("The {} opens a door.", entity.name)
+        }
+        (Some(lock), true) => match game
+            .inventory
+            .iter()
+            .find(|item| item.characteristics.lock.as_ref() == Some(lock))
+        {
+            Some(key) => format!("You unlock the door with the {}.", key.name),
+            None => {
+                messages.push(gen_message(
+                    &format!("The door is locked, it needs the {} key.", lock),
+                    MessageKind::Info,
+                    game.turn,
+                ));
+                return false;
+            }
+        },
+        _ => return false,
     };
 
-    grass_texture.to_owned()
+    if is_player || game.vision.is_visible(&door.pos) {
+        messages.push(gen_message(&text, MessageKind::Info, game.turn));
+    }
+
+    set_door_open(&mut game.buildings[door_index], true);
+    true
 }
 
-fn get_random_ground() -> TileNames {
-    let mut rng = rand::thread_rng();
-    let random_ground: u8 = rng.gen_range(1..=100);
+// opens the closed doors next to the player, or closes the open ones when there are none,
+// returns whether any door changed
+pub fn use_doors(entity: &Entity, game: &mut Game, messages: &mut Vec<Message>) -> bool {
+    let around: Vec<usize> = ["LEFT", "RIGHT", "UP", "DOWN"]
+        .iter()
+        .map(|side| get_position_towards(&entity.pos, side))
+        .filter_map(|pos| {
+            game.buildings
+                .iter()
+                .position(|building| building.pos == pos && building.mode == EntityMode::Door)
+        })
+        .collect();
+
+    let closed: Vec<usize> = around
+        .iter()
+        .copied()
+        .filter(|index| !game.buildings[*index].characteristics.walkable)
+        .collect();
+
+    if !closed.is_empty() {
+        let mut opened = false;
+
+        for index in closed {
+            opened |= open_door(entity, game, index, messages);
+        }
 
-    let grass_texture = match random_ground {
-        0..=66 => get_random_dirt(),
-        67..=100 => get_random_grass(),
-        _ => unreachable!(),
+        return opened;
+    }
+
+    let mut closed_any = false;
+    let mut blocked = false;
+
+    for index in around {
+        let pos = game.buildings[index].pos;
+
+        if !position_free(&pos, &game.entities) || game.items.iter().any(|item| item.pos == pos) {
+            messages.push(gen_message(
+                "Something is in the way of the door.",
+                MessageKind::Info,
+                game.turn,
+            ));
+            blocked = true;
+            continue;
+        }
+
+        set_door_open(&mut game.buildings[index], false);
+        closed_any = true;
+    }
+
+    let text = match (closed_any, blocked) {
+        (true, _) => "You close the door.",
+        (false, false) => "There is no door around you.",
+        (false, true) => return false,
     };
 
-    grass_texture.to_owned()
+    messages.push(gen_message(text, MessageKind::Info, game.turn));
+    closed_any
 }
 
-fn get_random_brick_wall() -> TileNames {
-    let mut rng = rand::thread_rng();
-    let random_brick_wall: u8 = rng.gen_range(1..=100);
-    let grass_texture: &TileNames = match random_brick_wall {
-        1..=74 => &TileNames::BrickWall1,
-        75..=89 => &TileNames::BrickWall2,
-        90..=100 => &TileNames::BrickWall3,
-        _ => unreachable!(),
+// everything lying where the entity stands goes in the player's inventory
+pub fn pick_up(entity: &Entity, game: &mut Game, messages: &mut Vec<Message>) {
+    let (here, elsewhere): (Vec<Entity>, Vec<Entity>) = game
+        .items
+        .drain(..)
+        .partition(|item| item.pos == entity.pos);
+    game.items = elsewhere;
+
+    if here.is_empty() {
+        messages.push(gen_message(
+            "There is nothing here to pick up.",
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+
+    for item in here {
+        messages.push(gen_message(
+            &format!("You pick up the {}.", item.name),
+            MessageKind::Info,
+            game.turn,
+        ));
+        game.inventory.push(item);
+    }
+}
+
+// brings a creature up to date with its (possibly edited) template, without touching
+// where it is or how hurt it is
+// what it is doing and whose side it is on are left alone, a reload can't turn a foe into a
+// friend halfway through a fight, or anything into a second player
+pub fn apply_creature_template(entity: &mut Entity, template: &CreatureTemplate) {
+    let was_unhurt = entity.stats.health >= entity.stats.max_health;
+
+    entity.name = template.name.to_owned();
+    entity.characteristics.face = template.face.to_owned();
+    entity.characteristics.color = template.color;
+    entity.characteristics.opens_doors = template.opens_doors;
+    entity.characteristics.swims = template.swims;
+    entity.characteristics.digs = template.digs;
+    entity.dialogue = template.dialogue.clone();
+    entity.stats.max_health = template.health;
+    entity.stats.attack = template.attack;
+    entity.stats.health = match was_unhurt {
+        true => template.health,
+        false => entity.stats.health.min(template.health),
     };
+}
+
+// random creature of the given mode that is allowed to show up at this depth
+pub fn pick_creature(
+    creatures: &[CreatureTemplate],
+    mode: EntityMode,
+    depth: i32,
+) -> Option<&CreatureTemplate> {
+    let candidates: Vec<&CreatureTemplate> = creatures
+        .iter()
+        .filter(|creature| creature.mode() == mode && creature.depth <= depth)
+        .collect();
+
+    if candidates.is_empty() {
+        return None;
+    }
+
+    Some(Random::with_rng(|rng| {
+        candidates[rng.gen_range(0..candidates.len())]
+    }))
+}
+
+fn get_health_status(stats: &EntityStats) -> &'static str {
+    match stats.health * 4 / stats.max_health.max(1) {
+        4 => "unhurt",
+        2..=3 => "wounded",
+        _ => "badly wounded",
+    }
+}
+
+// what the look panel shows for a tile, terrain first and then whoever stands on it
+pub fn describe_tile(game: &Game, tile_pos: &Position) -> Vec<String> {
+    let mut lines: Vec<String> = vec![format!("Tile x: {}, y: {}", tile_pos.x, tile_pos.y)];
+
+    let in_tile = get_entities_in_tile(game, tile_pos);
 
-    grass_texture.to_owned()
-}
-
-pub fn gen_entity(
-    entity_mode: EntityMode,
-    entity_relationship: EntityRelationship,
-    position: Position,
-) -> Entity {
-    let entity_characteristics = EntityCharacteristics {
-        face: match entity_mode {
-            EntityMode::BrickWall => get_random_brick_wall(),
-            EntityMode::Player => TileNames::NakedPlayer,
-            EntityMode::NPC => TileNames::NPC,
-            EntityMode::Mob => TileNames::Outline,
-            EntityMode::Ground => get_random_ground(),
+    if in_tile.is_empty() {
+        lines.push("Nothing but darkness.".to_string());
+    }
+
+    for entity in in_tile.iter() {
+        match entity.mode {
+            EntityMode::BrickWall
+            | EntityMode::Ground
+            | EntityMode::ShallowWater
+            | EntityMode::Rubble
+            | EntityMode::DeepWater
+            | EntityMode::Lava => {
+                lines.push(format!("Terrain: {}", entity.name));
+
+                if let Some(effect) = Terrain::describe(entity.mode) {
+                    lines.push(effect.to_string());
+                }
+
+                match entity.characteristics.material {
+                    Some(Material::Bedrock) => {
+                        lines.push("Material: bedrock, nothing digs through it".to_string())
+                    }
+                    Some(material) => lines.push(format!(
+                        "Material: {}, {}/{}",
+                        format!("{:?}", material).to_lowercase(),
+                        entity.stats.health,
+                        entity.stats.max_health
+                    )),
+                    None => (),
+                }
+
+                if let Some(trap) = Traps::get_found_trap(game, tile_pos) {
+                    lines.push(format!("Trap: {}", Traps::get_name(trap.kind)));
+                }
+            }
+            EntityMode::Door => {
+                lines.push(format!("Terrain: {}", entity.name));
+
+                if let Some(lock) = &entity.characteristics.lock {
+                    lines.push(format!("It needs the {} key.", lock));
+                }
+            }
+            EntityMode::Item => lines.push(format!("Item: {}", entity.name)),
+            _ => {
+                lines.push(String::new());
+                lines.push(capitalize(&entity.name));
+                lines.push(format!(
+                    "Health: {}/{}",
+                    entity.stats.health, entity.stats.max_health
+                ));
+                lines.push(format!(
+                    "Status: {}, {}",
+                    match entity.relationship {
+                        EntityRelationship::Foe => "hostile",
+                        EntityRelationship::Friendly => "friendly",
+                        EntityRelationship::Neutral => "neutral",
+                        EntityRelationship::None => "indifferent",
+                    },
+                    get_health_status(&entity.stats)
+                ));
+                lines.push(format!(
+                    "AI: {}",
+                    match entity.ai {
+                        EntityAi::Player => "under your control",
+                        EntityAi::Wander => "wandering around",
+                        EntityAi::None => "none",
+                    }
+                ));
+            }
+        }
+    }
+
+    lines
+}
+
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+pub fn gen_message(text: &str, kind: MessageKind, turn: u32) -> Message {
+    Message {
+        text: text.to_string(),
+        kind,
+        turn,
+    }
+}
+
+pub fn get_position_towards(pos: &Position, side: &str) -> Position {
+    match side {
+        "LEFT" => Position {
+            x: pos.x - 1,
+            y: pos.y,
         },
-        color: match entity_relationship {
-            EntityRelationship::Foe => Color::RED,
-            EntityRelationship::Friendly => Color::GREEN,
-            EntityRelationship::Neutral => Color::WHITE,
-            EntityRelationship::None => Color::from_hex("333333").unwrap(),
+        "RIGHT" => Position {
+            x: pos.x + 1,
+            y: pos.y,
         },
-        walkable: match entity_mode {
-            EntityMode::BrickWall => false,
-            EntityMode::Player => false,
-            EntityMode::NPC => false,
-            EntityMode::Mob => false,
-            EntityMode::Ground => true,
+        "UP" => Position {
+            x: pos.x,
+            y: pos.y - 1,
         },
-    };
-
-    Entity {
-        id: Uuid::new_v4(),
-        mode: entity_mode,
-        relationship: entity_relationship,
-        characteristics: entity_characteristics,
-        pos: position,
+        "DOWN" => Position {
+            x: pos.x,
+            y: pos.y + 1,
+        },
+        _ => pos.to_owned(),
     }
 }
 
-pub fn update_player(entity: &mut Entity, d: &mut RaylibDrawHandle, game: &Game) {
-    if d.is_key_pressed(KeyboardKey::KEY_H) && can_move(entity, "LEFT", game) {
-        entity.pos.x -= 1;
+fn get_bump_message(target: &Position, game: &Game) -> Message {
+    // entities come after buildings, so the last one is whatever stands on top of the tile
+    let blocker = get_entities_in_tile(game, target).pop();
+
+    match blocker.as_ref().map(|entity| entity.mode) {
+        Some(EntityMode::Mob) => gen_message(
+            "A hostile creature blocks your way!",
+            MessageKind::Warning,
+            game.turn,
+        ),
+        Some(EntityMode::NPC) => gen_message(
+            "Someone friendly is standing in the way.",
+            MessageKind::Info,
+            game.turn,
+        ),
+        Some(EntityMode::BrickWall)
+            if blocker
+                .as_ref()
+                .and_then(|wall| wall.characteristics.material)
+                == Some(Material::Bedrock)
+                && get_player(game)
+                    .and_then(|player| Terrain::get_dig_power(player, game))
+                    .is_some() =>
+        {
+            gen_message(
+                "The rock here is too hard to dig.",
+                MessageKind::Info,
+                game.turn,
+            )
+        }
+        Some(EntityMode::BrickWall) => {
+            gen_message("You bump into a brick wall.", MessageKind::Info, game.turn)
+        }
+        Some(EntityMode::DeepWater) => gen_message(
+            "The water is too deep, you can't swim.",
+            MessageKind::Info,
+            game.turn,
+        ),
+        _ => gen_message("You can't go that way.", MessageKind::Info, game.turn),
     }
+}
 
-    if d.is_key_pressed(KeyboardKey::KEY_L) && can_move(entity, "RIGHT", game) {
-        entity.pos.x += 1;
+pub fn get_command_side(command: Command) -> Option<&'static str> {
+    match command {
+        Command::MoveLeft => Some("LEFT"),
+        Command::MoveRight => Some("RIGHT"),
+        Command::MoveUp => Some("UP"),
+        Command::MoveDown => Some("DOWN"),
+        _ => None,
     }
+}
+
+pub fn update_player(
+    entity: &mut Entity,
+    command: Command,
+    game: &Game,
+    messages: &mut Vec<Message>,
+) {
+    let side = match get_command_side(command) {
+        Some(side) => side,
+        None => return,
+    };
 
-    if d.is_key_pressed(KeyboardKey::KEY_J) && can_move(entity, "DOWN", game) {
-        entity.pos.y += 1;
+    if can_move(entity, side, game) {
+        entity.pos = get_position_towards(&entity.pos, side);
+    } else {
+        messages.push(get_bump_message(
+            &get_position_towards(&entity.pos, side),
+            game,
+        ));
     }
+}
 
-    if d.is_key_pressed(KeyboardKey::KEY_K) && can_move(entity, "UP", game) {
-        entity.pos.y -= 1;
+// only the player and its foes fight, everybody else just gets in the way
+pub fn is_hostile(attacker: &Entity, target: &Entity) -> bool {
+    match (attacker.mode, target.mode) {
+        (EntityMode::Player, _) => matches!(target.relationship, EntityRelationship::Foe),
+        (_, EntityMode::Player) => matches!(attacker.relationship, EntityRelationship::Foe),
+        _ => false,
     }
 }
 
+// index of the living entity this one would hit by moving towards side
+pub fn get_attack_target(entity: &Entity, side: &str, game: &Game) -> Option<usize> {
+    let target_pos = get_position_towards(&entity.pos, side);
+
+    game.entities.iter().position(|other| {
+        other.pos == target_pos && other.stats.health > 0 && is_hostile(entity, other)
+    })
+}
+
+// returns the damage done, anything left at zero health or below is dead
+pub fn attack(
+    attacker: &Entity,
+    target: &mut Entity,
+    turn: u32,
+    messages: &mut Vec<Message>,
+) -> i32 {
+    let damage = match attacker.stats.attack {
+        0 => 0,
+        attack => Random::with_rng(|rng| rng.gen_range(1..=attack)),
+    };
+
+    target.stats.health -= damage;
+
+    // attack 0 never hurts, that's a miss rather than a hit for nothing
+    let message = match (attacker.mode, target.stats.health > 0) {
+        (EntityMode::Player, _) if damage == 0 => gen_message(
+            &format!("You miss the {}.", target.name),
+            MessageKind::Info,
+            turn,
+        ),
+        (_, _) if damage == 0 => gen_message(
+            &format!("The {} misses you.", attacker.name),
+            MessageKind::Info,
+            turn,
+        ),
+        (EntityMode::Player, true) => gen_message(
+            &format!("You hit the {} for {}.", target.name, damage),
+            MessageKind::Info,
+            turn,
+        ),
+        (EntityMode::Player, false) => gen_message(
+            &format!("You kill the {}!", target.name),
+            MessageKind::Info,
+            turn,
+        ),
+        (_, true) => gen_message(
+            &format!("The {} hits you for {}.", attacker.name, damage),
+            MessageKind::Warning,
+            turn,
+        ),
+        (_, false) => gen_message(
+            &format!("The {} kills you...", attacker.name),
+            MessageKind::Warning,
+            turn,
+        ),
+    };
+
+    messages.push(message);
+
+    damage
+}
+
 pub fn can_move(entity: &Entity, side: &str, game: &Game) -> bool {
     if side == "LEFT" {
         // is there any entity that is directly to the left of this entity?
@@ -172,10 +712,11 @@ pub fn can_move(entity: &Entity, side: &str, game: &Game) -> bool {
 
         // is there any building that is directly to the left of this entity?
         for game_building in game.buildings.iter() {
-            if game_building.pos.x == (entity.pos.x - 1) && game_building.pos.y == entity.pos.y {
-                if !game_building.characteristics.walkable {
-                    return false;
-                }
+            if game_building.pos.x == (entity.pos.x - 1)
+                && game_building.pos.y == entity.pos.y
+                && !Terrain::can_enter(entity, game_building)
+            {
+                return false;
             }
         }
     }
@@ -190,10 +731,11 @@ pub fn can_move(entity: &Entity, side: &str, game: &Game) -> bool {
 
         // is there any building that is directly to the right of this entity?
         for game_building in game.buildings.iter() {
-            if game_building.pos.x == (entity.pos.x + 1) && game_building.pos.y == entity.pos.y {
-                if !game_building.characteristics.walkable {
-                    return false;
-                }
+            if game_building.pos.x == (entity.pos.x + 1)
+                && game_building.pos.y == entity.pos.y
+                && !Terrain::can_enter(entity, game_building)
+            {
+                return false;
             }
         }
     }
@@ -208,10 +750,11 @@ pub fn can_move(entity: &Entity, side: &str, game: &Game) -> bool {
 
         // is there any building that is directly to the up of this entity?
         for game_building in game.buildings.iter() {
-            if game_building.pos.x == entity.pos.x && game_building.pos.y == (entity.pos.y - 1) {
-                if !game_building.characteristics.walkable {
-                    return false;
-                }
+            if game_building.pos.x == entity.pos.x
+                && game_building.pos.y == (entity.pos.y - 1)
+                && !Terrain::can_enter(entity, game_building)
+            {
+                return false;
             }
         }
     }
@@ -226,10 +769,11 @@ pub fn can_move(entity: &Entity, side: &str, game: &Game) -> bool {
 
         // is there any entity that is directly to the down of this entity?
         for game_building in game.buildings.iter() {
-            if game_building.pos.x == entity.pos.x && game_building.pos.y == (entity.pos.y + 1) {
-                if !game_building.characteristics.walkable {
-                    return false;
-                }
+            if game_building.pos.x == entity.pos.x
+                && game_building.pos.y == (entity.pos.y + 1)
+                && !Terrain::can_enter(entity, game_building)
+            {
+                return false;
             }
         }
     }
@@ -237,57 +781,40 @@ pub fn can_move(entity: &Entity, side: &str, game: &Game) -> bool {
     true
 }
 
-pub fn move_random(entity: &mut Entity, game: &Game) {
-    let mut rng = rand::thread_rng();
-    let random_move: u8 = rng.gen_range(1..5);
-    let actual_move = match random_move {
+pub fn get_random_side() -> &'static str {
+    let random_move: u8 = Random::with_rng(|rng| rng.gen_range(1..5));
+
+    match random_move {
         1 => "LEFT",
         2 => "UP",
         3 => "DOWN",
         4 => "RIGHT",
         _ => unreachable!(),
-    };
-
-    println!(
-        "Entity with id {} is trying to move {}",
-        entity.id.to_string(),
-        actual_move
-    );
-
-    if random_move == 1 && can_move(&entity, actual_move, game) {
-        println!(
-            "Entity with id {} moved {}",
-            entity.id.to_string(),
-            actual_move
-        );
-        entity.pos.x -= 1;
-    }
-
-    if random_move == 2 && can_move(&entity, actual_move, game) {
-        println!(
-            "Entity with id {} moved {}",
-            entity.id.to_string(),
-            actual_move
-        );
-        entity.pos.y -= 1;
     }
+}
 
-    if random_move == 3 && can_move(&entity, actual_move, game) {
-        println!(
-            "Entity with id {} moved {}",
-            entity.id.to_string(),
-            actual_move
-        );
-        entity.pos.y += 1;
-    }
+pub fn move_random(entity: &mut Entity, actual_move: &str, game: &Game) {
+    Diagnostics::log(
+        Level::Trace,
+        TARGET_AI,
+        "entity trying to move",
+        &[("id", &entity.id), ("side", &actual_move)],
+    );
 
-    if random_move == 4 && can_move(&entity, actual_move, game) {
-        println!(
-            "Entity with id {} moved {}",
-            entity.id.to_string(),
-            actual_move
+    if can_move(entity, actual_move, game) {
+        entity.pos = get_position_towards(&entity.pos, actual_move);
+
+        Diagnostics::log(
+            Level::Debug,
+            TARGET_AI,
+            "entity moved",
+            &[
+                ("id", &entity.id),
+                ("side", &actual_move),
+                ("x", &entity.pos.x),
+                ("y", &entity.pos.y),
+            ],
         );
-        entity.pos.x += 1;
     }
 }
 
@@ -307,157 +834,82 @@ pub fn get_settings(
     let tile_height = window_settings.height / window_rows;
 
     let graphic_settings: GraphicSettings = GraphicSettings {
-        tile_width: tile_width,
-        tile_height: tile_height,
+        tile_width,
+        tile_height,
         font_offset_x: 10,
         font_offset_y: 5,
-        font_size: font_size,
+        font_size,
         rows: window_rows,
         columns: window_columns,
     };
 
-    return Settings {
+    let log_lines = 6;
+    let log_font_size = font_size;
+
+    let hud_settings: HudSettings = HudSettings {
+        log_lines,
+        log_font_size,
+        log_height: log_lines * (log_font_size + 2) + 10,
+    };
+
+    Settings {
         window: window_settings,
         graphic: graphic_settings,
-    };
+        hud: hud_settings,
+        debug: false,
+        animations: true,
+        keys: KeyPreset::Vi,
+        palette: Palette::Default,
+        world: WorldSettings {
+            generator: Generator::Arena,
+            npcs: 5,
+            mobs: 5,
+        },
+    }
 }
 
-pub fn position_free(pos: &Position, entities: &Vec<Entity>) -> bool {
-    for game_entity in entities.iter() {
-        if game_entity.pos.x == pos.x && game_entity.pos.y == pos.y {
-            return false;
-        }
+// same settings for a map of another size, tiles get resized to keep filling the window
+pub fn resize_map(settings: &Settings, rows: i32, columns: i32) -> Settings {
+    Settings {
+        debug: settings.debug,
+        animations: settings.animations,
+        keys: settings.keys,
+        palette: settings.palette,
+        world: settings.world,
+        ..get_settings(
+            settings.window.height,
+            settings.window.width,
+            rows,
+            columns,
+            settings.graphic.font_size,
+        )
     }
+}
 
-    true
+// same settings in a window of another size
+pub fn resize_window(settings: &Settings, height: i32, width: i32) -> Settings {
+    Settings {
+        debug: settings.debug,
+        animations: settings.animations,
+        keys: settings.keys,
+        palette: settings.palette,
+        world: settings.world,
+        ..get_settings(
+            height,
+            width,
+            settings.graphic.rows,
+            settings.graphic.columns,
+            settings.graphic.font_size,
+        )
+    }
 }
 
-pub fn get_all_keys() -> Vec<KeyboardKey> {
-    let mut all_keys: Vec<KeyboardKey> = Vec::new();
-
-    all_keys.push(KeyboardKey::KEY_A);
-    all_keys.push(KeyboardKey::KEY_B);
-    all_keys.push(KeyboardKey::KEY_C);
-    all_keys.push(KeyboardKey::KEY_D);
-    all_keys.push(KeyboardKey::KEY_E);
-    all_keys.push(KeyboardKey::KEY_F);
-    all_keys.push(KeyboardKey::KEY_G);
-    all_keys.push(KeyboardKey::KEY_H);
-    all_keys.push(KeyboardKey::KEY_I);
-    all_keys.push(KeyboardKey::KEY_J);
-    all_keys.push(KeyboardKey::KEY_K);
-    all_keys.push(KeyboardKey::KEY_L);
-    all_keys.push(KeyboardKey::KEY_M);
-    all_keys.push(KeyboardKey::KEY_N);
-    all_keys.push(KeyboardKey::KEY_O);
-    all_keys.push(KeyboardKey::KEY_P);
-    all_keys.push(KeyboardKey::KEY_Q);
-    all_keys.push(KeyboardKey::KEY_R);
-    all_keys.push(KeyboardKey::KEY_S);
-    all_keys.push(KeyboardKey::KEY_T);
-    all_keys.push(KeyboardKey::KEY_U);
-    all_keys.push(KeyboardKey::KEY_V);
-    all_keys.push(KeyboardKey::KEY_X);
-    all_keys.push(KeyboardKey::KEY_Y);
-    all_keys.push(KeyboardKey::KEY_W);
-    all_keys.push(KeyboardKey::KEY_Z);
-    all_keys.push(KeyboardKey::KEY_ONE);
-    all_keys.push(KeyboardKey::KEY_TWO);
-    all_keys.push(KeyboardKey::KEY_THREE);
-    all_keys.push(KeyboardKey::KEY_FOUR);
-    all_keys.push(KeyboardKey::KEY_FIVE);
-    all_keys.push(KeyboardKey::KEY_SIX);
-    all_keys.push(KeyboardKey::KEY_SEVEN);
-    all_keys.push(KeyboardKey::KEY_EIGHT);
-    all_keys.push(KeyboardKey::KEY_NINE);
-    all_keys.push(KeyboardKey::KEY_ZERO);
-    all_keys.push(KeyboardKey::KEY_DOWN);
-    all_keys.push(KeyboardKey::KEY_LEFT);
-    all_keys.push(KeyboardKey::KEY_RIGHT);
-    all_keys.push(KeyboardKey::KEY_UP);
-    all_keys.push(KeyboardKey::KEY_APOSTROPHE);
-    all_keys.push(KeyboardKey::KEY_BACK);
-    all_keys.push(KeyboardKey::KEY_BACKSLASH);
-    all_keys.push(KeyboardKey::KEY_BACKSPACE);
-    all_keys.push(KeyboardKey::KEY_CAPS_LOCK);
-    all_keys.push(KeyboardKey::KEY_COMMA);
-    all_keys.push(KeyboardKey::KEY_DELETE);
-    all_keys.push(KeyboardKey::KEY_END);
-    all_keys.push(KeyboardKey::KEY_ENTER);
-    all_keys.push(KeyboardKey::KEY_EQUAL);
-    all_keys.push(KeyboardKey::KEY_ESCAPE);
-    all_keys.push(KeyboardKey::KEY_F1);
-    all_keys.push(KeyboardKey::KEY_F2);
-    all_keys.push(KeyboardKey::KEY_F3);
-    all_keys.push(KeyboardKey::KEY_F4);
-    all_keys.push(KeyboardKey::KEY_F5);
-    all_keys.push(KeyboardKey::KEY_F6);
-    all_keys.push(KeyboardKey::KEY_F7);
-    all_keys.push(KeyboardKey::KEY_F8);
-    all_keys.push(KeyboardKey::KEY_F9);
-    all_keys.push(KeyboardKey::KEY_F10);
-    all_keys.push(KeyboardKey::KEY_F11);
-    all_keys.push(KeyboardKey::KEY_F12);
-    all_keys.push(KeyboardKey::KEY_GRAVE);
-    all_keys.push(KeyboardKey::KEY_HOME);
-    all_keys.push(KeyboardKey::KEY_INSERT);
-    all_keys.push(KeyboardKey::KEY_KB_MENU);
-    all_keys.push(KeyboardKey::KEY_KP_0);
-    all_keys.push(KeyboardKey::KEY_KP_1);
-    all_keys.push(KeyboardKey::KEY_KP_2);
-    all_keys.push(KeyboardKey::KEY_KP_3);
-    all_keys.push(KeyboardKey::KEY_KP_4);
-    all_keys.push(KeyboardKey::KEY_KP_5);
-    all_keys.push(KeyboardKey::KEY_KP_6);
-    all_keys.push(KeyboardKey::KEY_KP_7);
-    all_keys.push(KeyboardKey::KEY_KP_8);
-    all_keys.push(KeyboardKey::KEY_KP_9);
-    all_keys.push(KeyboardKey::KEY_KP_ENTER);
-    all_keys.push(KeyboardKey::KEY_KP_ADD);
-    all_keys.push(KeyboardKey::KEY_KP_DECIMAL);
-    all_keys.push(KeyboardKey::KEY_KP_DIVIDE);
-    all_keys.push(KeyboardKey::KEY_KP_ENTER);
-    all_keys.push(KeyboardKey::KEY_KP_EQUAL);
-    all_keys.push(KeyboardKey::KEY_KP_MULTIPLY);
-    all_keys.push(KeyboardKey::KEY_KP_SUBTRACT);
-    all_keys.push(KeyboardKey::KEY_LEFT_ALT);
-    all_keys.push(KeyboardKey::KEY_LEFT_BRACKET);
-    all_keys.push(KeyboardKey::KEY_LEFT_CONTROL);
-    all_keys.push(KeyboardKey::KEY_LEFT_SHIFT);
-    all_keys.push(KeyboardKey::KEY_LEFT_SUPER);
-    all_keys.push(KeyboardKey::KEY_MINUS);
-    all_keys.push(KeyboardKey::KEY_NULL);
-    all_keys.push(KeyboardKey::KEY_NUM_LOCK);
-    all_keys.push(KeyboardKey::KEY_PAGE_DOWN);
-    all_keys.push(KeyboardKey::KEY_PAGE_UP);
-    all_keys.push(KeyboardKey::KEY_PAUSE);
-    all_keys.push(KeyboardKey::KEY_PERIOD);
-    all_keys.push(KeyboardKey::KEY_PRINT_SCREEN);
-    all_keys.push(KeyboardKey::KEY_RIGHT_ALT);
-    all_keys.push(KeyboardKey::KEY_RIGHT_BRACKET);
-    all_keys.push(KeyboardKey::KEY_RIGHT_CONTROL);
-    all_keys.push(KeyboardKey::KEY_RIGHT_SHIFT);
-    all_keys.push(KeyboardKey::KEY_RIGHT_SUPER);
-    all_keys.push(KeyboardKey::KEY_SCROLL_LOCK);
-    all_keys.push(KeyboardKey::KEY_SEMICOLON);
-    all_keys.push(KeyboardKey::KEY_SLASH);
-    all_keys.push(KeyboardKey::KEY_SPACE);
-    all_keys.push(KeyboardKey::KEY_TAB);
-    all_keys.push(KeyboardKey::KEY_VOLUME_DOWN);
-    all_keys.push(KeyboardKey::KEY_VOLUME_UP);
-    all_keys.push(KeyboardKey::KEY_MENU);
-
-    all_keys
-}
-
-pub fn user_interacted(d: &RaylibDrawHandle) -> Option<KeyboardKey> {
-    let all_keys: Vec<KeyboardKey> = get_all_keys();
-
-    for key in all_keys {
-        if d.is_key_pressed(key) {
-            return Option::Some(key);
+pub fn position_free(pos: &Position, entities: &[Entity]) -> bool {
+    for game_entity in entities.iter() {
+        if game_entity.pos.x == pos.x && game_entity.pos.y == pos.y {
+            return false;
         }
     }
 
-    return Option::None;
+    true
 }
@@ -0,0 +1,566 @@
+use crate::color::Color;
+use crate::state::domain::{Entity, EntityMode, Position, Trap};
+use crate::state::helpers as StateHelpers;
+use crate::state::random as Random;
+use crate::state::terrain as Terrain;
+use crate::state::traps as Traps;
+use rand::Rng;
+use std::collections::VecDeque;
+
+// Map layouts, every tile of the map ends up either a wall, some ground (maybe water, lava
+// or rubble) or a door, with a few traps hidden in the ground. The walls around the edge
+// are bedrock, so digging never leads off the map.
+
+const ROOM_ATTEMPTS: i32 = 40;
+const ROOM_MIN_SIZE: i32 = 3;
+const ROOM_MAX_SIZE: i32 = 8;
+// odds for each door to get locked, as long as there are locks left
+const LOCK_CHANCE: f64 = 0.25;
+// what doors get locked with, and the colour of the matching key
+const LOCKS: [(&str, &str); 3] = [
+    ("brass", "D4AF37"),
+    ("copper", "B87333"),
+    ("silver", "C0C0C0"),
+];
+// one trap for this many ground tiles
+const TRAP_SPACING: usize = 100;
+// odds for each room but the first to get a pool, some lava or rubble
+const FEATURE_CHANCE: f64 = 0.4;
+// odds for each tile of a room with lava or rubble to get some
+const FEATURE_DENSITY: f64 = 0.5;
+// odds for each corridor tile to be rubble
+const CORRIDOR_RUBBLE_CHANCE: f64 = 0.1;
+
+pub struct Layout {
+    pub buildings: Vec<Entity>,
+    // lying around from the start, ex: keys
+    pub items: Vec<Entity>,
+    pub traps: Vec<Trap>,
+    // where the player starts
+    pub start: Position,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Room {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+}
+
+impl Room {
+    fn center(&self) -> Position {
+        Position {
+            x: self.x + self.width / 2,
+            y: self.y + self.height / 2,
+        }
+    }
+
+    fn contains(&self, pos: &Position) -> bool {
+        pos.x >= self.x
+            && pos.y >= self.y
+            && pos.x < self.x + self.width
+            && pos.y < self.y + self.height
+    }
+
+    // the same room, shrunk by margin on every side
+    fn inner(&self, margin: i32) -> Room {
+        Room {
+            x: self.x + margin,
+            y: self.y + margin,
+            width: self.width - 2 * margin,
+            height: self.height - 2 * margin,
+        }
+    }
+
+    // touching counts, rooms keep at least a wall between them
+    fn overlaps(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width
+            && other.x <= self.x + self.width
+            && self.y <= other.y + other.height
+            && other.y <= self.y + self.height
+    }
+
+    // the walls around the room, corners left out
+    fn ring(&self) -> Vec<Position> {
+        let mut tiles: Vec<Position> = Vec::new();
+
+        for x in self.x..self.x + self.width {
+            tiles.push(Position { x, y: self.y - 1 });
+            tiles.push(Position {
+                x,
+                y: self.y + self.height,
+            });
+        }
+
+        for y in self.y..self.y + self.height {
+            tiles.push(Position { x: self.x - 1, y });
+            tiles.push(Position {
+                x: self.x + self.width,
+                y,
+            });
+        }
+
+        tiles
+    }
+}
+
+pub fn arena(columns: i32, rows: i32) -> Layout {
+    let mut buildings: Vec<Entity> = Vec::new();
+
+    for n in 0..columns {
+        buildings.push(StateHelpers::gen_terrain(
+            EntityMode::BrickWall,
+            Position { x: n, y: 0 },
+        ));
+
+        buildings.push(StateHelpers::gen_terrain(
+            EntityMode::BrickWall,
+            Position { x: n, y: rows - 1 },
+        ));
+    }
+
+    for n in 0..rows {
+        buildings.push(StateHelpers::gen_terrain(
+            EntityMode::BrickWall,
+            Position { x: 0, y: n },
+        ));
+
+        buildings.push(StateHelpers::gen_terrain(
+            EntityMode::BrickWall,
+            Position {
+                x: columns - 1,
+                y: n,
+            },
+        ));
+    }
+
+    for y in 1..rows - 1 {
+        for x in 1..columns - 1 {
+            buildings.push(StateHelpers::gen_terrain(
+                EntityMode::Ground,
+                Position { x, y },
+            ));
+        }
+    }
+
+    let start = Position { x: 1, y: 1 };
+    harden_border(&mut buildings, columns, rows);
+
+    Layout {
+        traps: place_traps(&buildings, start, &[]),
+        buildings,
+        items: Vec::new(),
+        start,
+    }
+}
+
+fn harden_border(buildings: &mut [Entity], columns: i32, rows: i32) {
+    for building in buildings.iter_mut() {
+        let pos = building.pos;
+
+        if pos.x == 0 || pos.y == 0 || pos.x == columns - 1 || pos.y == rows - 1 {
+            StateHelpers::make_bedrock(building);
+        }
+    }
+}
+
+// on plain ground, away from where the player starts and from anything lying around
+fn place_traps(buildings: &[Entity], start: Position, items: &[Entity]) -> Vec<Trap> {
+    let mut spots: Vec<Position> = buildings
+        .iter()
+        .filter(|building| building.mode == EntityMode::Ground)
+        .map(|building| building.pos)
+        .filter(|pos| (pos.x - start.x).abs() > 1 || (pos.y - start.y).abs() > 1)
+        .filter(|pos| items.iter().all(|item| item.pos != *pos))
+        .collect();
+
+    let mut traps: Vec<Trap> = Vec::new();
+
+    for _ in 0..spots.len() / TRAP_SPACING {
+        let (index, kind) = Random::with_rng(|rng| {
+            (
+                rng.gen_range(0..spots.len()),
+                Traps::TRAP_KINDS[rng.gen_range(0..Traps::TRAP_KINDS.len())],
+            )
+        });
+
+        traps.push(Traps::gen_trap(kind, spots.swap_remove(index)));
+    }
+
+    traps
+}
+
+fn carve(floor: &mut [bool], columns: i32, x: i32, y: i32) {
+    floor[(y * columns + x) as usize] = true;
+}
+
+fn is_floor(floor: &[bool], columns: i32, rows: i32, x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && x < columns && y < rows && floor[(y * columns + x) as usize]
+}
+
+// floor squeezed between two walls, going on at both other ends
+fn is_doorway(floor: &[bool], columns: i32, rows: i32, pos: &Position) -> bool {
+    let at = |dx: i32, dy: i32| is_floor(floor, columns, rows, pos.x + dx, pos.y + dy);
+
+    at(0, 0)
+        && ((!at(-1, 0) && !at(1, 0) && at(0, -1) && at(0, 1))
+            || (!at(0, -1) && !at(0, 1) && at(-1, 0) && at(1, 0)))
+}
+
+// floor that can be walked to from start without going through any of the blocked tiles
+fn reachable(
+    floor: &[bool],
+    columns: i32,
+    rows: i32,
+    start: Position,
+    blocked: &[Position],
+) -> Vec<bool> {
+    let mut seen = vec![false; floor.len()];
+    let mut queue: VecDeque<Position> = VecDeque::from([start]);
+    seen[(start.y * columns + start.x) as usize] = true;
+
+    while let Some(current) = queue.pop_front() {
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let next = Position {
+                x: current.x + dx,
+                y: current.y + dy,
+            };
+
+            if !is_floor(floor, columns, rows, next.x, next.y) || blocked.contains(&next) {
+                continue;
+            }
+
+            let index = (next.y * columns + next.x) as usize;
+
+            if !seen[index] {
+                seen[index] = true;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    seen
+}
+
+// a random floor tile that can be walked to from start without going through any of the
+// blocked tiles, other than start itself and the taken tiles
+fn pick_reachable_spot(
+    floor: &[bool],
+    columns: i32,
+    rows: i32,
+    start: Position,
+    blocked: &[Position],
+    taken: &[Position],
+) -> Option<Position> {
+    let open = reachable(floor, columns, rows, start, blocked);
+    let spots: Vec<Position> = (0..rows)
+        .flat_map(|y| (0..columns).map(move |x| Position { x, y }))
+        .filter(|pos| {
+            open[(pos.y * columns + pos.x) as usize] && *pos != start && !taken.contains(pos)
+        })
+        .collect();
+
+    if spots.is_empty() {
+        return None;
+    }
+
+    Some(Random::with_rng(|rng| spots[rng.gen_range(0..spots.len())]))
+}
+
+// a few doors get locked, each key lying somewhere it can be picked up without going
+// through any locked door
+fn lock_doors(
+    floor: &[bool],
+    columns: i32,
+    rows: i32,
+    start: Position,
+    doors: &[Position],
+) -> (Vec<(Position, &'static str)>, Vec<Entity>) {
+    let mut locked: Vec<(Position, &'static str)> = Vec::new();
+    let mut keys: Vec<Entity> = Vec::new();
+
+    for door in doors.iter() {
+        if locked.len() == LOCKS.len() || !Random::with_rng(|rng| rng.gen_bool(LOCK_CHANCE)) {
+            continue;
+        }
+
+        let mut blocked: Vec<Position> = locked.iter().map(|(pos, _)| *pos).collect();
+        blocked.push(*door);
+
+        // locking this one can't shut away the keys already lying around
+        let open = reachable(floor, columns, rows, start, &blocked);
+        if keys
+            .iter()
+            .any(|key| !open[(key.pos.y * columns + key.pos.x) as usize])
+        {
+            continue;
+        }
+
+        let mut taken = doors.to_vec();
+        taken.extend(keys.iter().map(|key| key.pos));
+
+        let spot = match pick_reachable_spot(floor, columns, rows, start, &blocked, &taken) {
+            Some(spot) => spot,
+            None => continue,
+        };
+
+        let (lock, color) = LOCKS[locked.len()];
+
+        keys.push(StateHelpers::gen_key(
+            lock,
+            Color::from_hex(color).unwrap(),
+            spot,
+        ));
+        locked.push((*door, lock));
+    }
+
+    (locked, keys)
+}
+
+// what the floor is made of, tile by tile: pools, lava and rubble go inside rooms and a
+// tile away from their walls, so the ways in and out of a room always stay connected, and
+// corridors get some rubble
+fn add_features(
+    rooms: &[Room],
+    floor: &[bool],
+    columns: i32,
+    doors: &[Position],
+) -> Vec<EntityMode> {
+    let mut terrain = vec![EntityMode::Ground; floor.len()];
+    let index = |pos: &Position| (pos.y * columns + pos.x) as usize;
+
+    // the first room is where the player starts
+    for room in rooms.iter().skip(1) {
+        if !Random::with_rng(|rng| rng.gen_bool(FEATURE_CHANCE)) {
+            continue;
+        }
+
+        let (inside, middle) = (room.inner(1), room.inner(2));
+        let kind = Random::with_rng(|rng| rng.gen_range(0..3));
+
+        for y in inside.y..inside.y + inside.height {
+            for x in inside.x..inside.x + inside.width {
+                let pos = Position { x, y };
+                let dense = Random::with_rng(|rng| rng.gen_bool(FEATURE_DENSITY));
+
+                terrain[index(&pos)] = match kind {
+                    0 if middle.contains(&pos) => EntityMode::DeepWater,
+                    0 => EntityMode::ShallowWater,
+                    1 if dense => EntityMode::Lava,
+                    2 if dense => EntityMode::Rubble,
+                    _ => EntityMode::Ground,
+                };
+            }
+        }
+    }
+
+    for (i, is_floor) in floor.iter().enumerate() {
+        let pos = Position {
+            x: i as i32 % columns,
+            y: i as i32 / columns,
+        };
+
+        let corridor =
+            *is_floor && !doors.contains(&pos) && rooms.iter().all(|room| !room.contains(&pos));
+
+        if corridor && Random::with_rng(|rng| rng.gen_bool(CORRIDOR_RUBBLE_CHANCE)) {
+            terrain[i] = EntityMode::Rubble;
+        }
+    }
+
+    terrain
+}
+
+// an L shaped corridor, going one way or the other first
+fn connect(floor: &mut [bool], columns: i32, from: Position, to: Position) {
+    let horizontal_first = Random::with_rng(|rng| rng.gen_bool(0.5));
+    let corner = match horizontal_first {
+        true => Position { x: to.x, y: from.y },
+        false => Position { x: from.x, y: to.y },
+    };
+
+    for (start, end) in [(from, corner), (corner, to)] {
+        for x in start.x.min(end.x)..=start.x.max(end.x) {
+            for y in start.y.min(end.y)..=start.y.max(end.y) {
+                carve(floor, columns, x, y);
+            }
+        }
+    }
+}
+
+// rooms thrown at random on solid rock, each one dug to the previous one
+pub fn rooms(columns: i32, rows: i32) -> Layout {
+    // not even a single room fits, a plain arena it is
+    if columns < ROOM_MIN_SIZE + 2 || rows < ROOM_MIN_SIZE + 2 {
+        return arena(columns, rows);
+    }
+
+    let mut floor = vec![false; (columns * rows) as usize];
+    let mut rooms: Vec<Room> = Vec::new();
+
+    for _ in 0..ROOM_ATTEMPTS {
+        let room = Random::with_rng(|rng| {
+            let width = rng.gen_range(ROOM_MIN_SIZE..=ROOM_MAX_SIZE.min(columns - 2));
+            let height = rng.gen_range(ROOM_MIN_SIZE..=ROOM_MAX_SIZE.min(rows - 2));
+
+            Room {
+                x: rng.gen_range(1..=columns - 1 - width),
+                y: rng.gen_range(1..=rows - 1 - height),
+                width,
+                height,
+            }
+        });
+
+        if rooms.iter().any(|other| other.overlaps(&room)) {
+            continue;
+        }
+
+        for y in room.y..room.y + room.height {
+            for x in room.x..room.x + room.width {
+                carve(&mut floor, columns, x, y);
+            }
+        }
+
+        if let Some(previous) = rooms.last() {
+            connect(&mut floor, columns, previous.center(), room.center());
+        }
+
+        rooms.push(room);
+    }
+
+    // where a corridor goes through the wall of a room, one door is enough when two rooms
+    // are a corridor tile apart
+    let mut doors: Vec<Position> = Vec::new();
+
+    for pos in rooms.iter().flat_map(|room| room.ring()) {
+        let next_to_door = doors
+            .iter()
+            .any(|door| (door.x - pos.x).abs() + (door.y - pos.y).abs() <= 1);
+
+        if is_doorway(&floor, columns, rows, &pos) && !next_to_door {
+            doors.push(pos);
+        }
+    }
+
+    let start = rooms[0].center();
+    let terrain = add_features(&rooms, &floor, columns, &doors);
+
+    // keys don't end up at the bottom of a pool or in lava
+    let safe: Vec<bool> = floor
+        .iter()
+        .zip(terrain.iter())
+        .map(|(is_floor, mode)| *is_floor && !Terrain::is_hazard(*mode))
+        .collect();
+
+    let (locked, mut items) = lock_doors(&safe, columns, rows, start, &doors);
+
+    // something to dig with, found without unlocking anything
+    let mut taken = doors.clone();
+    taken.extend(items.iter().map(|item| item.pos));
+    let blocked: Vec<Position> = locked.iter().map(|(pos, _)| *pos).collect();
+
+    if let Some(spot) = pick_reachable_spot(&safe, columns, rows, start, &blocked, &taken) {
+        items.push(StateHelpers::gen_pickaxe(spot));
+    }
+
+    let mut buildings: Vec<Entity> = Vec::new();
+
+    for y in 0..rows {
+        for x in 0..columns {
+            let pos = Position { x, y };
+            let mode = match floor[(y * columns + x) as usize] {
+                true if doors.contains(&pos) => EntityMode::Door,
+                true => terrain[(y * columns + x) as usize],
+                false => EntityMode::BrickWall,
+            };
+
+            let mut building = StateHelpers::gen_terrain(mode, pos);
+
+            if let Some((_, lock)) = locked.iter().find(|(door, _)| *door == pos) {
+                StateHelpers::lock_door(&mut building, lock);
+            }
+
+            buildings.push(building);
+        }
+    }
+
+    harden_border(&mut buildings, columns, rows);
+
+    Layout {
+        traps: place_traps(&buildings, start, &items),
+        buildings,
+        items,
+        start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doors_sit_in_doorways_and_keys_can_be_reached_without_them() {
+        let (columns, rows) = (48, 32);
+
+        for seed in 0..20 {
+            Random::seed(seed);
+            let layout = rooms(columns, rows);
+
+            let floor: Vec<bool> = layout
+                .buildings
+                .iter()
+                .map(|building| building.mode != EntityMode::BrickWall)
+                .collect();
+            let doors: Vec<&Entity> = layout
+                .buildings
+                .iter()
+                .filter(|building| building.mode == EntityMode::Door)
+                .collect();
+
+            assert!(!doors.is_empty(), "seed {}", seed);
+
+            for door in doors.iter() {
+                assert!(
+                    is_doorway(&floor, columns, rows, &door.pos),
+                    "seed {}: door at {:?}",
+                    seed,
+                    door.pos
+                );
+            }
+
+            // the player doesn't get through pools, lava or a door without its key
+            let safe: Vec<bool> = layout
+                .buildings
+                .iter()
+                .zip(floor.iter())
+                .map(|(building, is_floor)| *is_floor && !Terrain::is_hazard(building.mode))
+                .collect();
+            let locked: Vec<Position> = doors
+                .iter()
+                .filter(|door| door.characteristics.lock.is_some())
+                .map(|door| door.pos)
+                .collect();
+            let open = reachable(&safe, columns, rows, layout.start, &locked);
+
+            assert_eq!(
+                layout
+                    .items
+                    .iter()
+                    .filter(|item| item.name == "pickaxe")
+                    .count(),
+                1
+            );
+
+            for item in layout.items.iter() {
+                assert!(
+                    open[(item.pos.y * columns + item.pos.x) as usize],
+                    "seed {}: {} at {:?}",
+                    seed,
+                    item.name,
+                    item.pos
+                );
+            }
+        }
+    }
+}
@@ -3,3 +3,22 @@ pub mod domain;
 pub mod helpers;
 
 pub mod core;
+
+pub mod fov;
+
+pub mod random;
+
+pub mod mapgen;
+
+pub mod path;
+
+pub mod travel;
+
+pub mod traps;
+
+pub mod terrain;
+
+pub mod dialogue;
+
+#[cfg(test)]
+pub mod test_support;
@@ -0,0 +1,155 @@
+use crate::state::domain::{Entity, Game, Position};
+use crate::state::helpers as StateHelpers;
+use crate::state::terrain as Terrain;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// Cheapest walks from a tile, only going through the ones the player has seen, that can be
+// walked on (or are doors it can open), where no creature in sight is standing and where
+// the player didn't find a trap. Slow terrain costs more (see state::terrain), deep water
+// and lava are never walked into.
+
+const SIDES: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+fn get_cost(building: &Entity) -> Option<u32> {
+    match StateHelpers::is_passable(building) && !Terrain::is_hazard(building.mode) {
+        true => Some(Terrain::get_move_cost(building.mode)),
+        false => None,
+    }
+}
+
+// what stepping onto each tile the player knows about costs, indexed like Vision
+fn known_costs(game: &Game) -> Vec<Option<u32>> {
+    let (columns, rows) = (game.settings.graphic.columns, game.settings.graphic.rows);
+    let mut tiles = vec![None; (columns * rows).max(0) as usize];
+
+    for building in game.buildings.iter() {
+        let pos = building.pos;
+
+        if pos.x >= 0 && pos.y >= 0 && pos.x < columns && pos.y < rows {
+            tiles[(pos.y * columns + pos.x) as usize] =
+                get_cost(building).filter(|_| game.vision.is_explored(&pos));
+        }
+    }
+
+    for entity in game.entities.iter() {
+        let pos = entity.pos;
+
+        if game.vision.is_visible(&pos) && pos.x < columns && pos.y < rows {
+            tiles[(pos.y * columns + pos.x) as usize] = None;
+        }
+    }
+
+    for trap in game.traps.iter().filter(|trap| !trap.hidden) {
+        let pos = trap.pos;
+
+        if pos.x >= 0 && pos.y >= 0 && pos.x < columns && pos.y < rows {
+            tiles[(pos.y * columns + pos.x) as usize] = None;
+        }
+    }
+
+    tiles
+}
+
+pub fn is_known_passable(game: &Game, pos: &Position) -> bool {
+    game.vision.is_explored(pos)
+        && game
+            .buildings
+            .iter()
+            .any(|building| building.pos == *pos && get_cost(building).is_some())
+}
+
+// every tile from one end to the other, both included, None if there is no known way
+pub fn find_path(game: &Game, from: Position, to: Position) -> Option<Vec<Position>> {
+    find_path_to(game, from, |pos| *pos == to)
+}
+
+// same, towards the closest tile that is_goal likes
+pub fn find_path_to(
+    game: &Game,
+    from: Position,
+    is_goal: impl Fn(&Position) -> bool,
+) -> Option<Vec<Position>> {
+    let columns = game.settings.graphic.columns;
+    let costs = known_costs(game);
+    let index = |pos: &Position| (pos.y * columns + pos.x) as usize;
+    let inside = |pos: &Position| {
+        pos.x >= 0 && pos.y >= 0 && pos.x < columns && pos.y < game.settings.graphic.rows
+    };
+
+    if !inside(&from) {
+        return None;
+    }
+
+    // cheapest first, ties go to whichever tile was reached first
+    let mut came_from: Vec<Option<Position>> = vec![None; costs.len()];
+    let mut spent: Vec<u32> = vec![u32::MAX; costs.len()];
+    let mut queue: BinaryHeap<Reverse<(u32, u32, i32, i32)>> = BinaryHeap::new();
+    let mut pushed = 0;
+    came_from[index(&from)] = Some(from);
+    spent[index(&from)] = 0;
+    queue.push(Reverse((0, pushed, from.x, from.y)));
+
+    while let Some(Reverse((cost, _, x, y))) = queue.pop() {
+        let current = Position { x, y };
+
+        // reached again for cheaper since it was queued
+        if cost > spent[index(&current)] {
+            continue;
+        }
+
+        if is_goal(&current) {
+            let mut path = vec![current];
+
+            while let Some(previous) = came_from[index(path.last().unwrap())] {
+                if previous == *path.last().unwrap() {
+                    break;
+                }
+
+                path.push(previous);
+            }
+
+            path.reverse();
+            return Some(path);
+        }
+
+        for (dx, dy) in SIDES {
+            let next = Position {
+                x: current.x + dx,
+                y: current.y + dy,
+            };
+
+            let step = match inside(&next).then(|| costs[index(&next)]).flatten() {
+                Some(step) => step,
+                None => continue,
+            };
+
+            if cost + step < spent[index(&next)] {
+                spent[index(&next)] = cost + step;
+                came_from[index(&next)] = Some(current);
+                pushed += 1;
+                queue.push(Reverse((cost + step, pushed, next.x, next.y)));
+            }
+        }
+    }
+
+    None
+}
+
+// a seen tile right next to one that hasn't been, walking there shows more of the map
+pub fn borders_unexplored(game: &Game, pos: &Position) -> bool {
+    let (columns, rows) = (game.settings.graphic.columns, game.settings.graphic.rows);
+
+    SIDES.iter().any(|(dx, dy)| {
+        let side = Position {
+            x: pos.x + dx,
+            y: pos.y + dy,
+        };
+
+        side.x >= 0
+            && side.y >= 0
+            && side.x < columns
+            && side.y < rows
+            && !game.vision.is_explored(&side)
+    })
+}
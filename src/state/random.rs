@@ -0,0 +1,24 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+
+// Every roll the game makes goes through here instead of thread_rng, so a seed is enough to
+// get the same map, the same spawns and (given the same commands) the same whole game back.
+// Each thread gets its own generator, tests running side by side don't mix their rolls.
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+pub fn seed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// a seed nobody picked, for games started without one
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+pub fn with_rng<T>(roll: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| roll(&mut rng.borrow_mut()))
+}
@@ -0,0 +1,332 @@
+use crate::state::domain::{Entity, EntityMode, Game, Material, Message, MessageKind, Position};
+use crate::state::helpers as StateHelpers;
+use crate::state::random as Random;
+use rand::Rng;
+
+// What walking over each kind of terrain takes: slow ground keeps whoever steps on it busy
+// for an extra turn, deep water keeps out anything that can't swim and lava burns. Walks
+// planned by state::path pay the same costs. Walls wear down under digging until they
+// crumble into rubble.
+
+// most damage stepping in lava does, rolls go from 1 to this
+const LAVA_DAMAGE: i32 = 5;
+
+// how much digging a wall takes, 0 for the ones that can't be dug at all
+pub fn get_wall_health(material: Material) -> i32 {
+    match material {
+        Material::Brick => 10,
+        Material::Bedrock => 0,
+    }
+}
+
+// turns it takes to step onto a walkable tile
+pub fn get_move_cost(mode: EntityMode) -> u32 {
+    match mode {
+        EntityMode::ShallowWater | EntityMode::Rubble => 2,
+        _ => 1,
+    }
+}
+
+// walkable, but nobody should end up there without meaning to
+pub fn is_hazard(mode: EntityMode) -> bool {
+    matches!(mode, EntityMode::DeepWater | EntityMode::Lava)
+}
+
+pub fn can_enter(entity: &Entity, building: &Entity) -> bool {
+    building.characteristics.walkable
+        && (building.mode != EntityMode::DeepWater || entity.characteristics.swims)
+}
+
+// what the look panel says about it, besides its name
+pub fn describe(mode: EntityMode) -> Option<&'static str> {
+    match mode {
+        EntityMode::ShallowWater => Some("Wading through it takes twice as long."),
+        EntityMode::Rubble => Some("Climbing over it takes twice as long."),
+        EntityMode::DeepWater => Some("Too deep to get in without swimming."),
+        EntityMode::Lava => Some("It burns whoever steps in."),
+        _ => None,
+    }
+}
+
+fn get_terrain<'a>(game: &'a Game, pos: &Position) -> Option<&'a Entity> {
+    game.buildings.iter().find(|building| building.pos == *pos)
+}
+
+// creatures other than the player never walk into lava on their own
+pub fn is_dangerous_towards(entity: &Entity, side: &str, game: &Game) -> bool {
+    let target = StateHelpers::get_position_towards(&entity.pos, side);
+
+    get_terrain(game, &target).is_some_and(|building| building.mode == EntityMode::Lava)
+}
+
+// how hard the entity hits the walls it digs into, None if it can't dig: monsters dig with
+// their own strength, the player needs a tool
+pub fn get_dig_power(entity: &Entity, game: &Game) -> Option<i32> {
+    if entity.characteristics.digs {
+        return Some(entity.stats.attack.max(1));
+    }
+
+    match entity.mode {
+        EntityMode::Player => game
+            .inventory
+            .iter()
+            .filter(|item| item.characteristics.digs)
+            .map(|item| item.stats.attack.max(1))
+            .max(),
+        _ => None,
+    }
+}
+
+// index of the wall moving towards side would dig into
+pub fn get_dig_target(entity: &Entity, side: &str, game: &Game) -> Option<usize> {
+    get_dig_power(entity, game)?;
+    let target = StateHelpers::get_position_towards(&entity.pos, side);
+
+    game.buildings.iter().position(|building| {
+        building.pos == target
+            && building.mode == EntityMode::BrickWall
+            && building.stats.health > 0
+    })
+}
+
+// one blow at the wall, returns whether it came down (and left rubble behind)
+pub fn dig(
+    entity: &Entity,
+    game: &mut Game,
+    wall_index: usize,
+    messages: &mut Vec<Message>,
+) -> bool {
+    let power = get_dig_power(entity, game).unwrap_or(1);
+    let damage = Random::with_rng(|rng| rng.gen_range(1..=power));
+    let is_player = entity.mode == EntityMode::Player;
+
+    let wall = &mut game.buildings[wall_index];
+    wall.stats.health -= damage;
+
+    let (pos, name, through) = (wall.pos, wall.name.to_owned(), wall.stats.health <= 0);
+
+    if through {
+        *wall = StateHelpers::gen_terrain(EntityMode::Rubble, pos);
+    }
+
+    let text = match (is_player, through) {
+        (true, false) => format!("You dig into the {}.", name),
+        (true, true) => format!("You dig through the {}.", name),
+        (false, false) => format!("The {} claws at the {}.", entity.name, name),
+        (false, true) => format!("The {} tunnels through the {}.", entity.name, name),
+    };
+
+    if is_player || game.vision.is_visible(&pos) {
+        messages.push(StateHelpers::gen_message(
+            &text,
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+
+    through
+}
+
+// the extra turns slow ground takes go by one at a time, returns whether the entity spent
+// this one getting through
+pub fn is_busy(entity: &mut Entity, game: &Game, messages: &mut Vec<Message>) -> bool {
+    if entity.busy == 0 {
+        return false;
+    }
+
+    entity.busy -= 1;
+
+    if entity.mode == EntityMode::Player {
+        let text = match get_terrain(game, &entity.pos).map(|building| building.mode) {
+            Some(EntityMode::Rubble) => "You pick your way over the rubble.",
+            _ => "You wade through the water.",
+        };
+
+        messages.push(StateHelpers::gen_message(
+            text,
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+
+    true
+}
+
+// whatever the tile the entity just stepped on does to it
+pub fn enter_terrain(entity: &mut Entity, game: &Game, messages: &mut Vec<Message>) {
+    let mode = match get_terrain(game, &entity.pos) {
+        Some(building) => building.mode,
+        None => return,
+    };
+
+    entity.busy = get_move_cost(mode) - 1;
+
+    if mode != EntityMode::Lava {
+        return;
+    }
+
+    let damage = Random::with_rng(|rng| rng.gen_range(1..=LAVA_DAMAGE));
+    entity.stats.health -= damage;
+
+    let text = match (entity.mode, entity.stats.health > 0) {
+        (EntityMode::Player, true) => format!("The lava burns you for {}.", damage),
+        (EntityMode::Player, false) => "You burn to death in the lava...".to_string(),
+        (_, _) => format!("The {} steps into the lava.", entity.name),
+    };
+
+    if entity.mode == EntityMode::Player {
+        messages.push(StateHelpers::gen_message(
+            &text,
+            MessageKind::Warning,
+            game.turn,
+        ));
+    } else if game.vision.is_visible(&entity.pos) {
+        messages.push(StateHelpers::gen_message(
+            &text,
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::core as StateCore;
+    use crate::state::domain::{Command, EntityAi, EntityRelationship, GameEvent};
+    use crate::state::path as Pathfinding;
+    use crate::state::test_support as TestSupport;
+
+    // a 9x5 room, with the given terrain on the tiles that aren't plain ground
+    fn get_room_game(terrain: &[(i32, i32, EntityMode)]) -> Game {
+        let settings = StateHelpers::get_settings(100, 180, 5, 9, 20);
+        let buildings = TestSupport::tiles(&settings, |x, y| match (x, y) {
+            (0 | 8, _) | (_, 0 | 4) => EntityMode::BrickWall,
+            _ => terrain
+                .iter()
+                .find(|(tx, ty, _)| (*tx, *ty) == (x, y))
+                .map(|(_, _, mode)| *mode)
+                .unwrap_or(EntityMode::Ground),
+        });
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+
+        TestSupport::game(
+            settings,
+            buildings,
+            vec![StateHelpers::gen_creature(&player, Position { x: 1, y: 1 })],
+        )
+    }
+
+    #[test]
+    fn water_slows_deep_water_stops_and_lava_burns() {
+        let mut game = get_room_game(&[
+            (2, 1, EntityMode::ShallowWater),
+            (3, 1, EntityMode::DeepWater),
+            (1, 2, EntityMode::Lava),
+        ]);
+        let settings = game.settings;
+
+        // one turn to step in, one more to get through
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(game.entities[0].pos, Position { x: 2, y: 1 });
+        assert_eq!(game.entities[0].busy, 1);
+
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(game.entities[0].pos, Position { x: 2, y: 1 });
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "You wade through the water."
+        );
+
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(game.entities[0].pos, Position { x: 2, y: 1 });
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "The water is too deep, you can't swim."
+        );
+
+        game.entities[0].pos = Position { x: 1, y: 1 };
+        game = StateCore::update_game_states(&game, &settings, Command::MoveDown);
+        assert_eq!(game.entities[0].pos, Position { x: 1, y: 2 });
+        assert!(game.entities[0].stats.health < 10);
+
+        let burnt = 10 - game.entities[0].stats.health;
+        assert!(game.events.iter().any(|event| matches!(
+            event,
+            GameEvent::Hurt { pos, damage, .. } if *pos == Position { x: 1, y: 2 } && *damage == burnt
+        )));
+    }
+
+    #[test]
+    fn walks_go_around_slow_ground_when_it_is_cheaper() {
+        let mut terrain: Vec<(i32, i32, EntityMode)> = Vec::new();
+
+        for x in 2..=6 {
+            terrain.push((x, 1, EntityMode::ShallowWater));
+            terrain.push((x, 2, EntityMode::Rubble));
+        }
+
+        let mut game = get_room_game(&terrain);
+        game.vision.explored = vec![true; game.vision.explored.len()];
+
+        // straight through costs 11, around the bottom row 10
+        let path = Pathfinding::find_path(&game, Position { x: 1, y: 1 }, Position { x: 7, y: 1 })
+            .unwrap();
+        assert_eq!(path.len(), 11);
+        assert!(path.contains(&Position { x: 4, y: 3 }));
+
+        // with lava in the way there is no way at all, even if it could be walked on
+        game.buildings
+            .iter_mut()
+            .filter(|building| building.pos.x == 4 && building.mode != EntityMode::BrickWall)
+            .for_each(|building| {
+                *building = StateHelpers::gen_terrain(EntityMode::Lava, building.pos)
+            });
+
+        assert!(
+            Pathfinding::find_path(&game, Position { x: 1, y: 1 }, Position { x: 7, y: 1 })
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn a_pickaxe_digs_through_brick_but_not_bedrock() {
+        let mut game = get_room_game(&[]);
+        let settings = game.settings;
+
+        // without a pickaxe the wall just stops you
+        game = StateCore::update_game_states(&game, &settings, Command::MoveUp);
+        assert_eq!(game.entities[0].pos, Position { x: 1, y: 1 });
+        assert_eq!(get_dig_power(&game.entities[0], &game), None);
+
+        game.inventory
+            .push(StateHelpers::gen_pickaxe(Position { x: 0, y: 0 }));
+        let revision = game.terrain_revision;
+
+        for _ in 0..10 {
+            game = StateCore::update_game_states(&game, &settings, Command::MoveUp);
+        }
+
+        let wall = game
+            .buildings
+            .iter()
+            .find(|building| building.pos == Position { x: 1, y: 0 })
+            .unwrap();
+        assert_eq!(wall.mode, EntityMode::Rubble);
+        assert!(game.terrain_revision > revision);
+
+        // the edge of the map never gives
+        game.entities[0].pos = Position { x: 7, y: 1 };
+        game.buildings
+            .iter_mut()
+            .filter(|building| building.pos == Position { x: 8, y: 1 })
+            .for_each(StateHelpers::make_bedrock);
+
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+        assert_eq!(get_dig_target(&game.entities[0], "RIGHT", &game), None);
+        assert_eq!(
+            game.messages.last().unwrap().text,
+            "The rock here is too hard to dig."
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use crate::color::Color;
+use crate::content::creatures::CreatureTemplate;
+use crate::state::domain::{
+    Entity, EntityAi, EntityMode, EntityRelationship, Game, Position, Settings, Vision,
+};
+use crate::state::fov as FieldOfView;
+use crate::state::helpers as StateHelpers;
+
+// Creatures and maps put together by hand for the tests, so none of them need the content files
+
+// named and drawn after its id, with 10 health, 2 attack and none of the flags
+pub fn template(id: &str, ai: EntityAi, relationship: EntityRelationship) -> CreatureTemplate {
+    CreatureTemplate {
+        id: id.to_string(),
+        name: id.to_string(),
+        face: id.to_string(),
+        color: Color::WHITE,
+        health: 10,
+        attack: 2,
+        ai,
+        relationship,
+        depth: 0,
+        opens_doors: false,
+        swims: false,
+        digs: false,
+        dialogue: None,
+    }
+}
+
+// every tile of the map, mode_at picks what each one is made of
+pub fn tiles(settings: &Settings, mode_at: impl Fn(i32, i32) -> EntityMode) -> Vec<Entity> {
+    let mut buildings: Vec<Entity> = Vec::new();
+
+    for y in 0..settings.graphic.rows {
+        for x in 0..settings.graphic.columns {
+            buildings.push(StateHelpers::gen_terrain(mode_at(x, y), Position { x, y }));
+        }
+    }
+
+    buildings
+}
+
+// a first turn with an empty message log, seen from wherever the player stands
+pub fn game(settings: Settings, buildings: Vec<Entity>, entities: Vec<Entity>) -> Game {
+    let mut game = Game {
+        entities,
+        buildings,
+        items: Vec::new(),
+        inventory: Vec::new(),
+        traps: Vec::new(),
+        alarm: None,
+        settings,
+        messages: Vec::new(),
+        turn: 0,
+        terrain_revision: 0,
+        events: Vec::new(),
+        vision: Vision::default(),
+        conversation: None,
+    };
+
+    FieldOfView::update_vision(&mut game);
+    game
+}
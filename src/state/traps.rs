@@ -0,0 +1,408 @@
+use crate::state::domain::{
+    Alarm, Entity, EntityMode, Game, Message, MessageKind, Position, Trap, TrapKind,
+};
+use crate::state::fov as FieldOfView;
+use crate::state::helpers as StateHelpers;
+use crate::state::random as Random;
+use rand::Rng;
+use uuid::Uuid;
+
+// Hidden traps: what they do to whoever steps on them, and how the player finds them before
+// that happens. Monsters know where the traps of their level are, everybody else learns
+// about one by seeing it go off.
+
+pub const TRAP_KINDS: [TrapKind; 4] = [
+    TrapKind::Spike,
+    TrapKind::Teleport,
+    TrapKind::Alarm,
+    TrapKind::Pit,
+];
+
+const SPIKE_DAMAGE: i32 = 4;
+const PIT_DAMAGE: i32 = 2;
+// odds to get out of a pit, every move is a try
+const CLIMB_CHANCE: f64 = 0.5;
+// how long monsters keep coming after an alarm went off
+const ALARM_TURNS: u32 = 20;
+// odds for the player to notice a hidden trap right next to it, every turn
+const NOTICE_CHANCE: f64 = 0.2;
+// searching looks this far around, with these odds for each hidden trap
+const SEARCH_RADIUS: i32 = 2;
+const SEARCH_CHANCE: f64 = 0.5;
+
+pub fn gen_trap(kind: TrapKind, position: Position) -> Trap {
+    Trap {
+        id: Uuid::new_v4(),
+        kind,
+        pos: position,
+        hidden: true,
+        known_by: Vec::new(),
+    }
+}
+
+pub fn get_name(kind: TrapKind) -> &'static str {
+    match kind {
+        TrapKind::Spike => "spike trap",
+        TrapKind::Teleport => "teleport trap",
+        TrapKind::Alarm => "alarm trap",
+        TrapKind::Pit => "pit",
+    }
+}
+
+// the sprite it is drawn with once found
+pub fn get_face(kind: TrapKind) -> &'static str {
+    match kind {
+        TrapKind::Spike => "trap_spike",
+        TrapKind::Teleport => "trap_teleport",
+        TrapKind::Alarm => "trap_alarm",
+        TrapKind::Pit => "trap_pit",
+    }
+}
+
+// a trap the player already found
+pub fn get_found_trap<'a>(game: &'a Game, pos: &Position) -> Option<&'a Trap> {
+    game.traps
+        .iter()
+        .find(|trap| trap.pos == *pos && !trap.hidden)
+}
+
+// whether moving towards side would walk into a trap the entity knows about
+pub fn knows_trap_towards(entity: &Entity, side: &str, game: &Game) -> bool {
+    let target = StateHelpers::get_position_towards(&entity.pos, side);
+
+    game.traps
+        .iter()
+        .any(|trap| trap.pos == target && trap.known_by.contains(&entity.id))
+}
+
+// in a pit, moving is trying to climb out, returns whether the entity stays in for this turn
+pub fn is_stuck(entity: &Entity, game: &Game, messages: &mut Vec<Message>) -> bool {
+    let in_pit = game
+        .traps
+        .iter()
+        .any(|trap| trap.pos == entity.pos && trap.kind == TrapKind::Pit);
+
+    if !in_pit || Random::with_rng(|rng| rng.gen_bool(CLIMB_CHANCE)) {
+        return false;
+    }
+
+    if entity.mode == EntityMode::Player {
+        messages.push(StateHelpers::gen_message(
+            "You try to climb out of the pit.",
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+
+    true
+}
+
+// whatever trap is where the entity just stepped goes off
+pub fn spring_trap(entity: &mut Entity, game: &mut Game, messages: &mut Vec<Message>) {
+    let index = match game.traps.iter().position(|trap| trap.pos == entity.pos) {
+        Some(index) => index,
+        None => return,
+    };
+
+    let kind = game.traps[index].kind;
+    let name = get_name(kind);
+    let is_player = entity.mode == EntityMode::Player;
+
+    // whoever sees it go off knows where it is from now on
+    let mut witnesses = FieldOfView::get_witnesses(game, &entity.pos);
+    witnesses.push(entity.id);
+
+    let seen = match StateHelpers::get_player(game) {
+        Some(player) => witnesses.contains(&player.id),
+        None => false,
+    };
+
+    let trap = &mut game.traps[index];
+    trap.hidden &= !seen;
+
+    for id in witnesses {
+        if !trap.known_by.contains(&id) {
+            trap.known_by.push(id);
+        }
+    }
+
+    let text = match kind {
+        TrapKind::Spike | TrapKind::Pit => {
+            let most = match kind {
+                TrapKind::Spike => SPIKE_DAMAGE,
+                _ => PIT_DAMAGE,
+            };
+            let damage = Random::with_rng(|rng| rng.gen_range(1..=most));
+            entity.stats.health -= damage;
+
+            match (kind, is_player, entity.stats.health > 0) {
+                (TrapKind::Spike, true, true) => format!(
+                    "You step on a spike trap, the spikes hit you for {}.",
+                    damage
+                ),
+                (TrapKind::Spike, true, false) => {
+                    "You step on a spike trap, the spikes kill you...".to_string()
+                }
+                (_, true, true) => format!("You fall into a pit and take {} damage.", damage),
+                (_, true, false) => "You fall into a pit and break your neck...".to_string(),
+                (TrapKind::Spike, false, _) => format!("The {} steps on a {}.", entity.name, name),
+                (_, false, _) => format!("The {} falls into a pit.", entity.name),
+            }
+        }
+        TrapKind::Teleport => {
+            // anywhere but the trap and the tile it was stepped on from, the mover itself is
+            // still on the latter in game.entities until its turn is over
+            let mut except = vec![entity.pos];
+            let others: Vec<Entity> = game
+                .entities
+                .iter()
+                .filter(|other| {
+                    if other.id == entity.id {
+                        except.push(other.pos);
+                    }
+
+                    other.id != entity.id
+                })
+                .cloned()
+                .collect();
+
+            if let Some(to) =
+                StateHelpers::gen_random_position_except(&others, &game.buildings, &except)
+            {
+                entity.pos = to;
+            }
+
+            match is_player {
+                true => "You step on a teleport trap, the world spins around you!".to_string(),
+                false => format!("The {} vanishes into thin air.", entity.name),
+            }
+        }
+        TrapKind::Alarm => {
+            game.alarm = Some(Alarm {
+                pos: entity.pos,
+                turns: ALARM_TURNS,
+            });
+
+            match (is_player, seen) {
+                (true, _) => "You step on an alarm trap, a loud alarm goes off!".to_string(),
+                (false, true) => format!("The {} sets off an alarm trap!", entity.name),
+                // heard from anywhere on the level
+                (false, false) => "You hear a loud alarm in the distance.".to_string(),
+            }
+        }
+    };
+
+    if seen || kind == TrapKind::Alarm {
+        let message_kind = match is_player {
+            true => MessageKind::Warning,
+            false => MessageKind::Info,
+        };
+
+        messages.push(StateHelpers::gen_message(&text, message_kind, game.turn));
+    }
+}
+
+// hidden traps in sight around pos, each found with the given odds
+fn find_traps(
+    game: &mut Game,
+    pos: &Position,
+    radius: i32,
+    chance: f64,
+    text: &str,
+    messages: &mut Vec<Message>,
+) -> bool {
+    let mut found = false;
+
+    for trap in game.traps.iter_mut() {
+        let close = (trap.pos.x - pos.x).abs() <= radius && (trap.pos.y - pos.y).abs() <= radius;
+
+        if !trap.hidden || !close || !game.vision.is_visible(&trap.pos) {
+            continue;
+        }
+
+        if Random::with_rng(|rng| rng.gen_bool(chance)) {
+            trap.hidden = false;
+            found = true;
+
+            messages.push(StateHelpers::gen_message(
+                &format!("{} {}.", text, get_name(trap.kind)),
+                MessageKind::Warning,
+                game.turn,
+            ));
+        }
+    }
+
+    found
+}
+
+pub fn search(entity: &Entity, game: &mut Game, messages: &mut Vec<Message>) {
+    let found = find_traps(
+        game,
+        &entity.pos,
+        SEARCH_RADIUS,
+        SEARCH_CHANCE,
+        "You find a hidden",
+        messages,
+    );
+
+    if !found {
+        messages.push(StateHelpers::gen_message(
+            "You search around but find nothing.",
+            MessageKind::Info,
+            game.turn,
+        ));
+    }
+}
+
+// the player keeps an eye out without having to search, but only right next to it
+pub fn notice_traps(game: &mut Game, messages: &mut Vec<Message>) {
+    if let Some(pos) = StateHelpers::get_player(game).map(|player| player.pos) {
+        find_traps(
+            game,
+            &pos,
+            1,
+            NOTICE_CHANCE,
+            "You notice a hidden",
+            messages,
+        );
+    }
+}
+
+// monsters hearing an alarm head for it, None when there's nothing to go after
+pub fn get_alarm_side(entity: &Entity, game: &Game) -> Option<&'static str> {
+    let alarm = game.alarm?;
+
+    if entity.mode != EntityMode::Mob {
+        return None;
+    }
+
+    let (dx, dy) = (alarm.pos.x - entity.pos.x, alarm.pos.y - entity.pos.y);
+    let horizontal = match dx {
+        0 => None,
+        _ if dx < 0 => Some("LEFT"),
+        _ => Some("RIGHT"),
+    };
+    let vertical = match dy {
+        0 => None,
+        _ if dy < 0 => Some("UP"),
+        _ => Some("DOWN"),
+    };
+
+    // the longest way first, the other one when that's blocked
+    let sides = match dx.abs() >= dy.abs() {
+        true => [horizontal, vertical],
+        false => [vertical, horizontal],
+    };
+
+    sides
+        .into_iter()
+        .flatten()
+        .find(|side| StateHelpers::can_move(entity, side, game))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::creatures::CreatureTemplate;
+    use crate::state::core as StateCore;
+    use crate::state::domain::{Command, EntityAi, EntityRelationship, GameEvent};
+    use crate::state::test_support as TestSupport;
+
+    // a 7x3 corridor, walkable from (1, 1) to (5, 1), with a spike trap at (3, 1)
+    fn get_corridor_game(who: &CreatureTemplate, at: Position) -> Game {
+        let settings = StateHelpers::get_settings(60, 140, 3, 7, 20);
+        let buildings = TestSupport::tiles(&settings, |x, y| match (x, y) {
+            (1..=5, 1) => EntityMode::Ground,
+            _ => EntityMode::BrickWall,
+        });
+
+        let mut game = TestSupport::game(
+            settings,
+            buildings,
+            vec![StateHelpers::gen_creature(who, at)],
+        );
+        game.traps = vec![gen_trap(TrapKind::Spike, Position { x: 3, y: 1 })];
+        game
+    }
+
+    #[test]
+    fn stepping_on_a_hidden_trap_sets_it_off_and_shows_it() {
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let mut game = get_corridor_game(&player, Position { x: 2, y: 1 });
+        let settings = game.settings;
+
+        game = StateCore::update_game_states(&game, &settings, Command::MoveRight);
+
+        assert_eq!(game.entities[0].pos, Position { x: 3, y: 1 });
+        assert!(game.entities[0].stats.health < 10);
+
+        let hurt = 10 - game.entities[0].stats.health;
+        assert!(game.events.iter().any(|event| matches!(
+            event,
+            GameEvent::Hurt { pos, damage, .. } if *pos == Position { x: 3, y: 1 } && *damage == hurt
+        )));
+        assert!(!game.traps[0].hidden);
+        assert!(game.traps[0].known_by.contains(&game.entities[0].id));
+        assert!(game.messages[0]
+            .text
+            .starts_with("You step on a spike trap"));
+
+        let look = StateHelpers::describe_tile(&game, &Position { x: 3, y: 1 });
+        assert!(look.contains(&"Trap: spike trap".to_string()));
+    }
+
+    #[test]
+    fn searching_finds_traps_and_monsters_keep_off_the_ones_they_know() {
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let mut game = get_corridor_game(&player, Position { x: 1, y: 1 });
+        let settings = game.settings;
+
+        for _ in 0..100 {
+            if !game.traps[0].hidden {
+                break;
+            }
+
+            game = StateCore::update_game_states(&game, &settings, Command::Search);
+        }
+
+        assert!(!game.traps[0].hidden);
+        assert!(game
+            .messages
+            .iter()
+            .any(|message| message.text == "You find a hidden spike trap."));
+
+        // the ghost is boxed in at the end of the corridor, the trap its only way out
+        let ghost = TestSupport::template("ghost", EntityAi::Wander, EntityRelationship::Foe);
+        game.entities = vec![StateHelpers::gen_creature(&ghost, Position { x: 4, y: 1 })];
+        game.buildings
+            .iter_mut()
+            .filter(|building| building.pos == Position { x: 5, y: 1 })
+            .for_each(|building| building.characteristics.walkable = false);
+        game.traps[0].known_by = vec![game.entities[0].id];
+
+        for _ in 0..100 {
+            game = StateCore::update_game_states(&game, &settings, Command::Wait);
+            assert_eq!(game.entities[0].pos, Position { x: 4, y: 1 });
+        }
+    }
+
+    #[test]
+    fn a_teleport_trap_never_keeps_the_mover_on_it_or_where_it_came_from() {
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+        let mut start = get_corridor_game(&player, Position { x: 2, y: 1 });
+        start.traps = vec![gen_trap(TrapKind::Teleport, Position { x: 3, y: 1 })];
+        let settings = start.settings;
+
+        for _ in 0..50 {
+            let game = StateCore::update_game_states(&start, &settings, Command::MoveRight);
+            let pos = game.entities[0].pos;
+
+            assert_ne!(pos, Position { x: 3, y: 1 });
+            assert_ne!(pos, Position { x: 2, y: 1 });
+            assert!(game.events.iter().any(|event| matches!(
+                event,
+                GameEvent::Teleported { from, to, .. } if *from == Position { x: 3, y: 1 } && *to == pos
+            )));
+        }
+    }
+}
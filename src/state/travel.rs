@@ -0,0 +1,503 @@
+use crate::state::domain::{Command, EntityMode, Game, MessageKind, Position};
+use crate::state::helpers as StateHelpers;
+use crate::state::path as Pathfinding;
+use crate::state::traps as Traps;
+use std::collections::VecDeque;
+use uuid::Uuid;
+
+// Walking somewhere over several turns. Each step is a regular move command, so monsters
+// get their turns and animations play in between, and the walk stops by itself as soon as
+// something worth the player's attention shows up. Running, resting and commands typed
+// after a count go through here too.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Tile(Position),
+    // the closest place the player hasn't seen yet, picked again at every step
+    Unexplored,
+    // the same command, this many times in all
+    Repeat(Command, u32),
+    // one way until the walls on either side change, openings is what they looked like
+    // after the first step
+    Run {
+        command: Command,
+        openings: [bool; 2],
+    },
+    // waiting until healed
+    Rest,
+}
+
+#[derive(Debug, Clone)]
+pub struct Travel {
+    pub destination: Destination,
+    // where the player should be standing right now, then every tile left to walk
+    pub path: VecDeque<Position>,
+    // hostile creatures already in sight, they don't interrupt the walk again
+    pub seen: Vec<Uuid>,
+    // the player's health at the last step, losing some stops everything
+    pub health: i32,
+    // how many traps the player had found, finding another one stops everything
+    pub traps: usize,
+    pub steps: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TravelStep {
+    Move(Command),
+    Arrived,
+    // nowhere left to explore
+    Explored,
+    // done resting
+    Rested,
+    // why it stopped, for the message log
+    Interrupted(String),
+}
+
+fn get_command_towards(from: &Position, to: &Position) -> Option<Command> {
+    match (to.x - from.x, to.y - from.y) {
+        (-1, 0) => Some(Command::MoveLeft),
+        (1, 0) => Some(Command::MoveRight),
+        (0, -1) => Some(Command::MoveUp),
+        (0, 1) => Some(Command::MoveDown),
+        _ => None,
+    }
+}
+
+// where a command leaves the player, anything but a move stays put
+fn get_position_after(pos: &Position, command: Command) -> Position {
+    let (dx, dy) = match command {
+        Command::MoveLeft => (-1, 0),
+        Command::MoveRight => (1, 0),
+        Command::MoveUp => (0, -1),
+        Command::MoveDown => (0, 1),
+        _ => (0, 0),
+    };
+
+    Position {
+        x: pos.x + dx,
+        y: pos.y + dy,
+    }
+}
+
+// whether the tiles on both sides of a move can be walked on, a change means a junction,
+// a doorway or the end of a room
+fn get_openings(game: &Game, pos: &Position, command: Command) -> [bool; 2] {
+    let sides = match command {
+        Command::MoveLeft | Command::MoveRight => [(0, -1), (0, 1)],
+        _ => [(-1, 0), (1, 0)],
+    };
+
+    sides.map(|(dx, dy)| {
+        Pathfinding::is_known_passable(
+            game,
+            &Position {
+                x: pos.x + dx,
+                y: pos.y + dy,
+            },
+        )
+    })
+}
+
+// a side passage right there, that neither the tile before nor the one after have
+fn is_junction(game: &Game, pos: &Position, command: Command) -> bool {
+    let after = get_position_after(pos, command);
+    let before = Position {
+        x: 2 * pos.x - after.x,
+        y: 2 * pos.y - after.y,
+    };
+    let [here, before, after] = [pos, &before, &after].map(|pos| get_openings(game, pos, command));
+
+    (0..2).any(|side| here[side] && !before[side] && !after[side])
+}
+
+// bumping into it opens it, the player stays where it is for that turn
+fn is_closed_door(game: &Game, pos: &Position) -> bool {
+    game.buildings.iter().any(|building| {
+        building.pos == *pos
+            && building.mode == EntityMode::Door
+            && !building.characteristics.walkable
+    })
+}
+
+fn is_move(command: Command) -> bool {
+    StateHelpers::get_command_side(command).is_some()
+}
+
+fn found_traps(game: &Game) -> usize {
+    game.traps.iter().filter(|trap| !trap.hidden).count()
+}
+
+// hostile creatures the player can see right now
+fn visible_foes(game: &Game) -> Vec<Uuid> {
+    let player = match StateHelpers::get_player(game) {
+        Some(player) => player,
+        None => return Vec::new(),
+    };
+
+    game.entities
+        .iter()
+        .filter(|entity| {
+            StateHelpers::is_hostile(player, entity) && game.vision.is_visible(&entity.pos)
+        })
+        .map(|entity| entity.id)
+        .collect()
+}
+
+fn new_travel(game: &Game, destination: Destination, seen: Vec<Uuid>) -> Option<Travel> {
+    // nobody walks off in the middle of a conversation
+    if game.conversation.is_some() {
+        return None;
+    }
+
+    let player = StateHelpers::get_player(game)?;
+
+    Some(Travel {
+        destination,
+        path: VecDeque::from([player.pos]),
+        seen,
+        health: player.stats.health,
+        traps: found_traps(game),
+        steps: 0,
+    })
+}
+
+pub fn start_travel(game: &Game, to: Position) -> Option<Travel> {
+    let player = StateHelpers::get_player(game)?;
+    let path = Pathfinding::find_path(game, player.pos, to)?;
+
+    // already there, nothing to walk
+    if path.len() < 2 {
+        return None;
+    }
+
+    let mut travel = new_travel(game, Destination::Tile(to), visible_foes(game))?;
+    travel.path = path.into();
+
+    Some(travel)
+}
+
+// commands that take several turns: explore, rest, a move with shift held (run) and any
+// gameplay command typed after a count, None for everything else. Exploring, running and
+// resting don't care which creatures were already in sight, any of them stops it.
+pub fn start_for_command(game: &Game, command: Command, count: u32, run: bool) -> Option<Travel> {
+    match command {
+        Command::Explore => new_travel(game, Destination::Unexplored, Vec::new()),
+        Command::Rest => new_travel(game, Destination::Rest, Vec::new()),
+        _ if run && is_move(command) => {
+            let openings = [false; 2];
+            new_travel(game, Destination::Run { command, openings }, Vec::new())
+        }
+        _ if count > 1
+            && (is_move(command) || matches!(command, Command::Wait | Command::Search)) =>
+        {
+            new_travel(
+                game,
+                Destination::Repeat(command, count),
+                visible_foes(game),
+            )
+        }
+        _ => None,
+    }
+}
+
+pub fn next_travel_step(game: &Game, travel: &mut Travel) -> TravelStep {
+    let player = match StateHelpers::get_player(game) {
+        Some(player) => player,
+        None => return TravelStep::Interrupted(String::new()),
+    };
+
+    // walked into somebody with something to say
+    if game.conversation.is_some() {
+        return TravelStep::Interrupted(String::new());
+    }
+
+    if player.stats.health < travel.health {
+        return TravelStep::Interrupted("You are hurt!".to_string());
+    }
+
+    travel.health = player.stats.health;
+
+    // still getting through slow ground, whatever is done this turn goes into that
+    if player.busy > 0 {
+        return TravelStep::Move(Command::Wait);
+    }
+
+    match travel.destination {
+        Destination::Unexplored => {
+            if player.stats.health * 2 <= player.stats.max_health {
+                return TravelStep::Interrupted("You are too hurt to explore.".to_string());
+            }
+
+            let path = Pathfinding::find_path_to(game, player.pos, |pos| {
+                Pathfinding::borders_unexplored(game, pos)
+            });
+
+            match path {
+                Some(path) => travel.path = path.into_iter().skip(1).collect(),
+                None => return TravelStep::Explored,
+            }
+        }
+        // the last step didn't go as planned, ex: something bumped into the player
+        _ => {
+            if travel.path.pop_front() != Some(player.pos) {
+                return TravelStep::Interrupted("You stop.".to_string());
+            }
+        }
+    }
+
+    // already in the log, no need to say it again
+    if found_traps(game) > travel.traps {
+        return TravelStep::Interrupted(String::new());
+    }
+
+    let foes = visible_foes(game);
+
+    if let Some(foe) = game
+        .entities
+        .iter()
+        .find(|entity| foes.contains(&entity.id) && !travel.seen.contains(&entity.id))
+    {
+        return TravelStep::Interrupted(format!("You see a {}.", foe.name));
+    }
+
+    // something to pick up, only worth stopping for when walking around on its own
+    if let Some(item) = game.items.iter().find(|item| item.pos == player.pos) {
+        if travel.steps > 0
+            && matches!(
+                travel.destination,
+                Destination::Unexplored | Destination::Run { .. }
+            )
+        {
+            return TravelStep::Interrupted(format!("You find a {}.", item.name));
+        }
+    }
+
+    let command = match &mut travel.destination {
+        Destination::Tile(_) | Destination::Unexplored => match travel.path.front() {
+            Some(next) => match get_command_towards(&player.pos, next) {
+                Some(command) => command,
+                None => return TravelStep::Interrupted("You stop.".to_string()),
+            },
+            None => return TravelStep::Arrived,
+        },
+        Destination::Repeat(_, times) if travel.steps >= *times => return TravelStep::Arrived,
+        Destination::Repeat(command, _) => *command,
+        Destination::Run { command, openings } => {
+            let here = get_openings(game, &player.pos, *command);
+
+            match travel.steps {
+                0 => (),
+                // nothing to compare the first tile with yet, a junction right there stops it
+                1 if is_junction(game, &player.pos, *command) => return TravelStep::Arrived,
+                1 => *openings = here,
+                _ if *openings != here => return TravelStep::Arrived,
+                _ => (),
+            }
+
+            let next = get_position_after(&player.pos, *command);
+            let blocked = game.entities.iter().any(|entity| entity.pos == next)
+                || !Pathfinding::is_known_passable(game, &next)
+                || is_closed_door(game, &next);
+
+            if blocked {
+                return TravelStep::Arrived;
+            }
+
+            *command
+        }
+        Destination::Rest if player.stats.health >= player.stats.max_health => {
+            return TravelStep::Rested;
+        }
+        Destination::Rest => Command::Wait,
+    };
+
+    let next = get_position_after(&player.pos, command);
+    let blocked = next != player.pos
+        && (game.entities.iter().any(|entity| entity.pos == next)
+            || !Pathfinding::is_known_passable(game, &next));
+
+    if blocked {
+        return TravelStep::Interrupted("Something is in the way.".to_string());
+    }
+
+    // walking into a known trap takes doing it on purpose
+    if let Some(trap) = Traps::get_found_trap(game, &next).filter(|_| next != player.pos) {
+        return TravelStep::Interrupted(format!(
+            "There is a {} in the way.",
+            Traps::get_name(trap.kind)
+        ));
+    }
+
+    // the tile walk already knows where it's going, unless a door holds the player back
+    let opening = is_closed_door(game, &next);
+
+    match travel.destination {
+        Destination::Tile(_) | Destination::Unexplored if opening => {
+            travel.path.push_front(player.pos)
+        }
+        Destination::Tile(_) | Destination::Unexplored => (),
+        _ if opening => travel.path.push_back(player.pos),
+        _ => travel.path.push_back(next),
+    }
+
+    travel.steps += 1;
+    TravelStep::Move(command)
+}
+
+fn log(game: &mut Game, text: &str) {
+    game.messages.push(StateHelpers::gen_message(
+        text,
+        MessageKind::Info,
+        game.turn,
+    ));
+}
+
+// what's left to do with a step, None once the travel is over (and logged, if interrupted)
+pub fn take_travel_step(game: &mut Game, travel: &mut Travel) -> Option<Command> {
+    match next_travel_step(game, travel) {
+        TravelStep::Move(command) => return Some(command),
+        TravelStep::Arrived => (),
+        TravelStep::Explored => log(game, "The level is explored, nothing left to see."),
+        TravelStep::Rested => log(game, "You feel rested."),
+        TravelStep::Interrupted(reason) if !reason.is_empty() => log(game, &reason),
+        TravelStep::Interrupted(_) => (),
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::domain::{EntityAi, EntityMode, EntityRelationship};
+    use crate::state::test_support as TestSupport;
+
+    // a 7x5 room split by a wall with a single gap at the bottom
+    fn get_split_room_game() -> Game {
+        let settings = StateHelpers::get_settings(140, 140, 5, 7, 20);
+        let buildings = TestSupport::tiles(&settings, |x, y| match (x, y) {
+            (0 | 6, _) | (_, 0 | 4) => EntityMode::BrickWall,
+            (3, 1..=2) => EntityMode::BrickWall,
+            _ => EntityMode::Ground,
+        });
+        let player = TestSupport::template("player", EntityAi::Player, EntityRelationship::Neutral);
+
+        let mut game = TestSupport::game(
+            settings,
+            buildings,
+            vec![StateHelpers::gen_creature(&player, Position { x: 1, y: 1 })],
+        );
+
+        // the other half is behind the wall, as if it had been seen before
+        game.vision.explored = vec![true; game.vision.explored.len()];
+        game
+    }
+
+    #[test]
+    fn travel_goes_around_walls_and_stops_for_new_foes() {
+        let mut game = get_split_room_game();
+
+        let mut travel = start_travel(&game, Position { x: 5, y: 1 }).unwrap();
+        assert_eq!(travel.path.len(), 9);
+
+        assert_eq!(
+            next_travel_step(&game, &mut travel),
+            TravelStep::Move(Command::MoveRight)
+        );
+
+        let ghost = TestSupport::template("ghost", EntityAi::Wander, EntityRelationship::Foe);
+        game.entities[0].pos = Position { x: 2, y: 1 };
+        game.entities
+            .push(StateHelpers::gen_creature(&ghost, Position { x: 2, y: 3 }));
+
+        assert_eq!(
+            next_travel_step(&game, &mut travel),
+            TravelStep::Interrupted("You see a ghost.".to_string())
+        );
+    }
+
+    #[test]
+    fn exploring_heads_for_unseen_tiles_until_there_are_none() {
+        let mut game = get_split_room_game();
+        let mut travel = start_for_command(&game, Command::Explore, 0, false).unwrap();
+
+        assert_eq!(next_travel_step(&game, &mut travel), TravelStep::Explored);
+
+        // the far side of the wall was never seen, the way there is through the gap
+        for x in 4..7 {
+            for y in 0..5 {
+                let index = (y * 7 + x) as usize;
+                game.vision.explored[index] = false;
+            }
+        }
+
+        assert_eq!(
+            next_travel_step(&game, &mut travel),
+            TravelStep::Move(Command::MoveRight)
+        );
+        assert_eq!(travel.path.back(), Some(&Position { x: 3, y: 3 }));
+
+        game.entities[0].stats.health = 1;
+
+        assert_eq!(
+            next_travel_step(&game, &mut travel),
+            TravelStep::Interrupted("You are hurt!".to_string())
+        );
+
+        let mut travel = start_for_command(&game, Command::Explore, 0, false).unwrap();
+
+        assert_eq!(
+            next_travel_step(&game, &mut travel),
+            TravelStep::Interrupted("You are too hurt to explore.".to_string())
+        );
+    }
+
+    #[test]
+    fn running_stops_in_the_doorway_and_counts_run_out() {
+        let mut game = get_split_room_game();
+        game.entities[0].pos = Position { x: 1, y: 3 };
+
+        let mut run = start_for_command(&game, Command::MoveRight, 0, true).unwrap();
+
+        for x in 2..4 {
+            assert_eq!(
+                next_travel_step(&game, &mut run),
+                TravelStep::Move(Command::MoveRight)
+            );
+            game.entities[0].pos = Position { x, y: 3 };
+        }
+
+        // the wall above ends right in the gap
+        assert_eq!(next_travel_step(&game, &mut run), TravelStep::Arrived);
+
+        let mut repeat = start_for_command(&game, Command::MoveLeft, 2, false).unwrap();
+        assert!(start_for_command(&game, Command::MoveLeft, 1, false).is_none());
+
+        for x in [2, 1] {
+            assert_eq!(
+                next_travel_step(&game, &mut repeat),
+                TravelStep::Move(Command::MoveLeft)
+            );
+            game.entities[0].pos = Position { x, y: 3 };
+        }
+
+        assert_eq!(next_travel_step(&game, &mut repeat), TravelStep::Arrived);
+
+        // a corridor with a side passage right after the first step
+        let buildings = TestSupport::tiles(&game.settings, |x, y| match (x, y) {
+            (2, 1..=2) | (1..=5, 3) => EntityMode::Ground,
+            _ => EntityMode::BrickWall,
+        });
+        let mut game = TestSupport::game(game.settings, buildings, game.entities);
+        game.vision.explored = vec![true; game.vision.explored.len()];
+
+        let mut run = start_for_command(&game, Command::MoveRight, 0, true).unwrap();
+        assert_eq!(
+            next_travel_step(&game, &mut run),
+            TravelStep::Move(Command::MoveRight)
+        );
+        game.entities[0].pos = Position { x: 2, y: 3 };
+
+        assert_eq!(next_travel_step(&game, &mut run), TravelStep::Arrived);
+    }
+}
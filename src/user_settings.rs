@@ -0,0 +1,322 @@
+use crate::content::parser::{self as Parser, Section};
+use crate::content::ContentError;
+use crate::diagnostics as Diagnostics;
+use crate::diagnostics::{Level, TARGET_GAME};
+use crate::state::domain::{KeyPreset, Palette, Settings};
+use crate::state::helpers as StateHelpers;
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+
+// What the player picked in the options screen, kept between runs in a "[settings]" section
+// written with the same format as the content files. Keys missing from the file keep their
+// default value, so an older file still loads.
+
+const SETTINGS_KEYS: [&str; 8] = [
+    "window",
+    "map",
+    "tile_scale",
+    "font_size",
+    "keys",
+    "palette",
+    "animations",
+    "minimap",
+];
+
+// width and height, in pixels
+const WINDOW_SIZES: [(i32, i32); 5] =
+    [(640, 640), (800, 800), (960, 960), (1024, 768), (1280, 960)];
+// columns and rows, in tiles
+const MAP_SIZES: [(i32, i32); 4] = [(16, 16), (20, 20), (30, 24), (40, 30)];
+// 0 stretches the tiles to fit the window, anything else sizes the window after the tiles
+const TILE_SCALES: [i32; 5] = [0, 1, 2, 3, 4];
+const FONT_SIZES: [i32; 7] = [14, 16, 18, 20, 24, 28, 32];
+const KEY_PRESETS: [KeyPreset; 3] = [KeyPreset::Vi, KeyPreset::Wasd, KeyPreset::Arrows];
+const PALETTES: [Palette; 3] = [Palette::Default, Palette::HighContrast, Palette::Colorblind];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserOption {
+    WindowSize,
+    MapSize,
+    TileScale,
+    FontSize,
+    Keys,
+    Palette,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserSettings {
+    pub window_width: i32,
+    pub window_height: i32,
+    pub columns: i32,
+    pub rows: i32,
+    pub tile_scale: i32,
+    pub font_size: i32,
+    pub keys: KeyPreset,
+    pub palette: Palette,
+    pub animations: bool,
+    pub minimap: bool,
+}
+
+impl Default for UserSettings {
+    fn default() -> UserSettings {
+        UserSettings {
+            window_width: 800,
+            window_height: 800,
+            columns: 20,
+            rows: 20,
+            tile_scale: 0,
+            font_size: 20,
+            keys: KeyPreset::Vi,
+            palette: Palette::Default,
+            animations: true,
+            minimap: false,
+        }
+    }
+}
+
+// $XDG_CONFIG_HOME/poorguelike, then ~/.config/poorguelike, then next to the game
+pub fn settings_path() -> String {
+    let directory = match (env::var("XDG_CONFIG_HOME"), env::var("HOME")) {
+        (Ok(config), _) if !config.is_empty() => Path::new(&config).join("poorguelike"),
+        (_, Ok(home)) if !home.is_empty() => Path::new(&home).join(".config").join("poorguelike"),
+        _ => Path::new(".").to_path_buf(),
+    };
+
+    directory
+        .join("settings.cfg")
+        .to_string_lossy()
+        .into_owned()
+}
+
+// the next or previous option in a list, going around at both ends
+fn cycle<T: Copy + PartialEq>(options: &[T], current: T, step: i32) -> T {
+    let index = options
+        .iter()
+        .position(|option| *option == current)
+        .unwrap_or(0) as i32;
+
+    options[(index + step).rem_euclid(options.len() as i32) as usize]
+}
+
+// "WIDTHxHEIGHT", ex: 800x600
+pub fn parse_size(value: &str) -> Option<(i32, i32)> {
+    let (first, second) = value.split_once('x')?;
+
+    match (first.trim().parse(), second.trim().parse()) {
+        (Ok(first), Ok(second)) if first > 0 && second > 0 => Some((first, second)),
+        _ => None,
+    }
+}
+
+// reads a key if it's there, keeps the default otherwise
+fn read<T>(
+    section: &Section,
+    key: &str,
+    default: T,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<T, ContentError> {
+    match section.get(key) {
+        Some(entry) => parse(&entry.value).ok_or_else(|| {
+            section.error(entry.line, format!("invalid '{}': '{}'", key, entry.value))
+        }),
+        None => Ok(default),
+    }
+}
+
+// enums are written with their debug names
+fn named<T: Copy + Debug>(options: &[T]) -> impl Fn(&str) -> Option<T> + '_ {
+    move |value| {
+        options
+            .iter()
+            .find(|option| format!("{:?}", option) == value)
+            .copied()
+    }
+}
+
+pub fn load_user_settings(path: &str) -> Result<UserSettings, ContentError> {
+    let defaults = UserSettings::default();
+    let sections = Parser::read_file(path)?;
+
+    let section = match sections.iter().find(|section| section.name == "settings") {
+        Some(section) => section,
+        None => return Ok(defaults),
+    };
+
+    section.only_keys(&SETTINGS_KEYS)?;
+
+    let (window_width, window_height) = read(
+        section,
+        "window",
+        (defaults.window_width, defaults.window_height),
+        parse_size,
+    )?;
+    let (columns, rows) = read(
+        section,
+        "map",
+        (defaults.columns, defaults.rows),
+        parse_size,
+    )?;
+
+    Ok(UserSettings {
+        window_width,
+        window_height,
+        columns,
+        rows,
+        tile_scale: read(section, "tile_scale", defaults.tile_scale, |value| {
+            value.parse().ok().filter(|scale| *scale >= 0)
+        })?,
+        font_size: read(section, "font_size", defaults.font_size, |value| {
+            value.parse().ok().filter(|size| *size > 0)
+        })?,
+        keys: read(section, "keys", defaults.keys, named(&KEY_PRESETS))?,
+        palette: read(section, "palette", defaults.palette, named(&PALETTES))?,
+        animations: read(section, "animations", defaults.animations, |value| {
+            value.parse().ok()
+        })?,
+        minimap: read(section, "minimap", defaults.minimap, |value| {
+            value.parse().ok()
+        })?,
+    })
+}
+
+// no file yet means defaults, a broken one too but it gets reported
+pub fn load_or_default(path: &str) -> UserSettings {
+    if !Path::new(path).exists() {
+        return UserSettings::default();
+    }
+
+    match load_user_settings(path) {
+        Ok(user) => user,
+        Err(error) => {
+            Diagnostics::log(
+                Level::Warn,
+                TARGET_GAME,
+                "could not load settings, using the defaults",
+                &[("error", &error)],
+            );
+            UserSettings::default()
+        }
+    }
+}
+
+pub fn save_user_settings(path: &str, user: &UserSettings) -> Result<(), String> {
+    let mut text =
+        String::from("# poorguelike settings, written by the options screen\n\n[settings]\n");
+
+    text.push_str(&format!(
+        "window = {}x{}\n",
+        user.window_width, user.window_height
+    ));
+    text.push_str(&format!("map = {}x{}\n", user.columns, user.rows));
+    text.push_str(&format!("tile_scale = {}\n", user.tile_scale));
+    text.push_str(&format!("font_size = {}\n", user.font_size));
+    text.push_str(&format!("keys = {:?}\n", user.keys));
+    text.push_str(&format!("palette = {:?}\n", user.palette));
+    text.push_str(&format!("animations = {}\n", user.animations));
+    text.push_str(&format!("minimap = {}\n", user.minimap));
+
+    if let Some(directory) = Path::new(path).parent() {
+        fs::create_dir_all(directory)
+            .map_err(|error| format!("could not create {}: {}", directory.display(), error))?;
+    }
+
+    let temporary = format!("{}.tmp", path);
+
+    fs::write(&temporary, text)
+        .and_then(|_| fs::rename(&temporary, path))
+        .map_err(|error| format!("could not write {}: {}", path, error))
+}
+
+impl UserSettings {
+    // the game settings for a map of the given size, a game loaded from a save keeps its own
+    pub fn to_settings(&self, rows: i32, columns: i32, tile_size: i32) -> Settings {
+        let (width, height) = match self.tile_scale {
+            0 => (self.window_width, self.window_height),
+            scale => (columns * tile_size * scale, rows * tile_size * scale),
+        };
+
+        Settings {
+            animations: self.animations,
+            keys: self.keys,
+            palette: self.palette,
+            ..StateHelpers::get_settings(height, width, rows, columns, self.font_size)
+        }
+    }
+
+    pub fn change(&mut self, option: UserOption, step: i32) {
+        match option {
+            UserOption::WindowSize => {
+                (self.window_width, self.window_height) =
+                    cycle(&WINDOW_SIZES, (self.window_width, self.window_height), step)
+            }
+            UserOption::MapSize => {
+                (self.columns, self.rows) = cycle(&MAP_SIZES, (self.columns, self.rows), step)
+            }
+            UserOption::TileScale => self.tile_scale = cycle(&TILE_SCALES, self.tile_scale, step),
+            UserOption::FontSize => self.font_size = cycle(&FONT_SIZES, self.font_size, step),
+            UserOption::Keys => self.keys = cycle(&KEY_PRESETS, self.keys, step),
+            UserOption::Palette => self.palette = cycle(&PALETTES, self.palette, step),
+        }
+    }
+
+    // how the options screen shows an option
+    pub fn describe(&self, option: UserOption) -> String {
+        match option {
+            UserOption::WindowSize => match self.tile_scale {
+                0 => format!("Window: {}x{}", self.window_width, self.window_height),
+                _ => "Window: sized by the tiles".to_string(),
+            },
+            UserOption::MapSize => format!("Map: {}x{} (next game)", self.columns, self.rows),
+            UserOption::TileScale => match self.tile_scale {
+                0 => "Tile scale: fit the window".to_string(),
+                scale => format!("Tile scale: x{}", scale),
+            },
+            UserOption::FontSize => format!("Font size: {}", self.font_size),
+            UserOption::Keys => format!(
+                "Keys: {}",
+                match self.keys {
+                    KeyPreset::Vi => "HJKL",
+                    KeyPreset::Wasd => "WASD",
+                    KeyPreset::Arrows => "arrows",
+                }
+            ),
+            UserOption::Palette => format!(
+                "Colours: {}",
+                match self.palette {
+                    Palette::Default => "default",
+                    Palette::HighContrast => "high contrast",
+                    Palette::Colorblind => "colour blind",
+                }
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn saved_settings_load_back_the_same() {
+        let mut user = UserSettings::default();
+        user.change(UserOption::MapSize, 1);
+        user.change(UserOption::Keys, -1);
+        user.change(UserOption::Palette, 1);
+        user.minimap = true;
+
+        let path = env::temp_dir().join(format!("poorguelike-{}", Uuid::new_v4()));
+        let file = path.join("settings.cfg");
+        let file = file.to_str().unwrap();
+
+        save_user_settings(file, &user).unwrap();
+        let loaded = load_user_settings(file);
+        let _ = fs::remove_dir_all(&path);
+
+        assert_eq!(loaded.unwrap(), user);
+        assert_eq!((user.columns, user.rows), (30, 24));
+        assert_eq!(user.keys, KeyPreset::Arrows);
+    }
+}